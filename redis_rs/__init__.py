@@ -1,17 +1,20 @@
 __all__ = [
     "create_client",
+    "register_codec",
     "Client",
     "AsyncClient",
+    "StreamEntry",
     "exceptions",
 ]
 
 import socket
-from typing import List, Optional
+from typing import Dict, List, Optional
 from uuid import uuid4
 
 from .client_async import AsyncClient
-from .redis_rs import Client, exceptions
+from .redis_rs import Client, StreamEntry, exceptions
 from .redis_rs import create_client as _create_client
+from .redis_rs import register_codec
 
 
 def create_client(
@@ -26,6 +29,26 @@ def create_client(
     client_id: Optional[str] = None,
     features: Optional[List[str]] = None,
     max_delay_ms: Optional[int] = None,
+    protocol: Optional[int] = None,
+    tls: Optional[bool] = None,
+    tls_ca_certs: Optional[str] = None,
+    tls_certfile: Optional[str] = None,
+    tls_keyfile: Optional[str] = None,
+    tls_insecure: bool = False,
+    sentinels: Optional[List[str]] = None,
+    service_name: Optional[str] = None,
+    read_from: Optional[str] = None,
+    max_redirects: Optional[int] = None,
+    client_cache: Optional[Dict[str, float]] = None,
+    retry_attempts: Optional[int] = None,
+    retry_backoff: Optional[float] = None,
+    retry_max_backoff: Optional[float] = None,
+    retry_on: Optional[List[str]] = None,
+    retry_writes: bool = False,
+    connect_timeout: Optional[float] = None,
+    response_timeout: Optional[float] = None,
+    health_check_interval: Optional[float] = None,
+    health_check_failures: Optional[int] = None,
 ) -> Client:
     if not client_id:
         client_id = f"{socket.gethostname()}-{uuid4()}"
@@ -41,4 +64,24 @@ def create_client(
         client_id=client_id,
         features=features,
         max_delay=max_delay_ms,
+        protocol=protocol,
+        tls=tls,
+        tls_ca_certs=tls_ca_certs,
+        tls_certfile=tls_certfile,
+        tls_keyfile=tls_keyfile,
+        tls_insecure=tls_insecure,
+        sentinels=sentinels,
+        service_name=service_name,
+        read_from=read_from,
+        max_redirects=max_redirects,
+        client_cache=client_cache,
+        retry_attempts=retry_attempts,
+        retry_backoff=retry_backoff,
+        retry_max_backoff=retry_max_backoff,
+        retry_on=retry_on,
+        retry_writes=retry_writes,
+        connect_timeout=connect_timeout,
+        response_timeout=response_timeout,
+        health_check_interval=health_check_interval,
+        health_check_failures=health_check_failures,
     )
@@ -1,17 +1,45 @@
 __all__ = [
     "create_client",
+    "register_codec",
     "Client",
     "AsyncClient",
+    "StreamEntry",
     "exceptions",
 ]
 
-from typing import Dict, List, Optional
+from typing import Any, Callable, Dict, List, Optional
 
-from redis_rs.client_async import AsyncClient
+from redis_rs.client_async import AsyncClient, StreamEntry
 
 class exceptions:
-    class PoolError(Exception): ...
-    class RedisError(Exception): ...
+    class PoolError(Exception):
+        code: Optional[str]
+        detail: Optional[str]
+        command: Optional[str]
+        node: Optional[str]
+        slot: Optional[int]
+        retryable: bool
+
+    class RedisError(Exception):
+        code: Optional[str]
+        detail: Optional[str]
+        command: Optional[str]
+        node: Optional[str]
+        slot: Optional[int]
+        retryable: bool
+
+    class WatchError(RedisError): ...
+    class TimeoutError(RedisError): ...
+    class ConnectionError(RedisError): ...
+    class AuthenticationError(ConnectionError): ...
+    class ResponseError(RedisError): ...
+    class WrongTypeError(ResponseError): ...
+    class NoScriptError(ResponseError): ...
+    class BusyLoadingError(ResponseError): ...
+    class ClusterDownError(ResponseError): ...
+    class MovedError(ResponseError): ...
+    class AskError(ResponseError): ...
+    class ReadOnlyError(ResponseError): ...
 
 class Client:
     def status(self) -> Dict: ...
@@ -30,4 +58,30 @@ def create_client(
     client_id: Optional[str] = None,
     features: Optional[List[str]] = None,
     max_delay_ms: Optional[int] = None,
+    protocol: Optional[int] = None,
+    tls: Optional[bool] = None,
+    tls_ca_certs: Optional[str] = None,
+    tls_certfile: Optional[str] = None,
+    tls_keyfile: Optional[str] = None,
+    tls_insecure: bool = False,
+    sentinels: Optional[List[str]] = None,
+    service_name: Optional[str] = None,
+    read_from: Optional[str] = None,
+    max_redirects: Optional[int] = None,
+    client_cache: Optional[Dict[str, float]] = None,
+    retry_attempts: Optional[int] = None,
+    retry_backoff: Optional[float] = None,
+    retry_max_backoff: Optional[float] = None,
+    retry_on: Optional[List[str]] = None,
+    retry_writes: bool = False,
+    connect_timeout: Optional[float] = None,
+    response_timeout: Optional[float] = None,
+    health_check_interval: Optional[float] = None,
+    health_check_failures: Optional[int] = None,
 ) -> Client: ...
+
+def register_codec(
+    name: str,
+    decode: Callable[[bytes], Any],
+    encode: Optional[Callable[[Any], bytes]] = None,
+) -> None: ...
@@ -1,29 +1,124 @@
-from typing import Dict, List, Literal, Mapping, Optional, Union, overload
+from typing import Any, Dict, List, Literal, Mapping, Optional, Tuple, Union, overload
 
 from redis_rs.types import Arg, Encoding, Result
 
+class Pipeline:
+    def add(self, *args: Arg, encoding: Optional[Encoding] = None) -> "Pipeline": ...
+    def __len__(self) -> int: ...
+    async def execute(self) -> List[Result]: ...
+
+class Transaction:
+    def __aiter__(self) -> "Transaction": ...
+    async def __anext__(self) -> "Transaction": ...
+    async def __aenter__(self) -> "Transaction": ...
+    async def __aexit__(self, *args, **kwargs) -> bool: ...
+    async def execute(self, *args: Arg, encoding: Optional[Encoding] = None) -> Result: ...
+    def add(self, *args: Arg, encoding: Optional[Encoding] = None) -> "Transaction": ...
+    def __len__(self) -> int: ...
+    async def exec(self) -> List[Result]: ...
+
+class Subscription:
+    def __aiter__(self) -> "Subscription": ...
+    async def __anext__(self) -> Dict[str, Any]: ...
+    def close(self) -> None: ...
+
+class ScanIter:
+    def __aiter__(self) -> "ScanIter": ...
+    async def __anext__(self) -> Any: ...
+
+class Script:
+    sha: str
+    async def __call__(
+        self,
+        keys: Optional[List[str]] = None,
+        args: Optional[List[Arg]] = None,
+        *,
+        encoding: Optional[Encoding] = None,
+    ) -> Result: ...
+
+class StreamEntry:
+    stream: str
+    id: str
+    fields: Dict[str, Any]
+    ms: int
+    seq: int
+    @staticmethod
+    def parse_id(id: Arg) -> Tuple[int, Optional[int]]: ...
+
+class Call:
+    name: str
+    args: List[bytes]
+    keys: List[bytes]
+    node: Optional[str]
+
 class AsyncClient:
     client_id: str
     def status(self) -> Dict: ...
-    async def execute(self, *args: Arg, encoding: Optional[Encoding] = None) -> Result: ...
-    async def fetch_bytes(self, *args: Arg) -> bytes: ...
-    async def fetch_str(self, *args: Arg) -> str: ...
-    async def fetch_int(self, *args: Arg) -> int: ...
-    async def fetch_float(self, *args: Arg) -> float: ...
-    async def fetch_dict(self, *args: Arg, encoding: Optional[Encoding] = None) -> dict: ...
-    async def fetch_scores(self, *args: Arg) -> Dict[str, float]: ...
+    def metrics(self) -> Dict[str, Dict[str, Dict[str, Any]]]: ...
+    def metrics_prometheus(self) -> str: ...
+    def add_hook(self, hook: Any) -> None: ...
+    def remove_hook(self, hook: Any) -> bool: ...
+    async def execute(
+        self, *args: Arg, encoding: Optional[Encoding] = None, timeout: Optional[float] = None
+    ) -> Result: ...
+    def pipeline(self) -> Pipeline: ...
+    def transaction(self, *watch: str, retries: int = 0) -> Transaction: ...
+    async def subscribe(self, *channels: str, encoding: Optional[Encoding] = None) -> Subscription: ...
+    async def psubscribe(self, *patterns: str, encoding: Optional[Encoding] = None) -> Subscription: ...
+    async def publish(self, channel: str, message: Arg) -> int: ...
+    async def fetch_bytes(self, *args: Arg, timeout: Optional[float] = None) -> bytes: ...
+    async def fetch_str(self, *args: Arg, timeout: Optional[float] = None) -> str: ...
+    async def fetch_int(self, *args: Arg, timeout: Optional[float] = None) -> int: ...
+    async def fetch_dict(
+        self, *args: Arg, encoding: Optional[Encoding] = None, timeout: Optional[float] = None
+    ) -> dict: ...
+    async def fetch_scores(self, *args: Arg, timeout: Optional[float] = None) -> Dict[str, float]: ...
     async def exists(self, *keys: str) -> int: ...
     async def expire(self, key: str, seconds: int, option: Optional[str] = None) -> int: ...
     async def delete(self, *keys: str) -> int: ...
     async def keys(self, pattern: str, encoding: Optional[str] = None) -> List[str]: ...
+    def scan_iter(
+        self,
+        match: Optional[str] = None,
+        count: Optional[int] = None,
+        type: Optional[str] = None,
+        *,
+        encoding: Optional[Encoding] = None,
+    ) -> ScanIter: ...
+    def sscan_iter(
+        self, key: str, match: Optional[str] = None, count: Optional[int] = None, *, encoding: Optional[Encoding] = None
+    ) -> ScanIter: ...
+    def hscan_iter(
+        self, key: str, match: Optional[str] = None, count: Optional[int] = None, *, encoding: Optional[Encoding] = None
+    ) -> ScanIter: ...
+    def zscan_iter(
+        self, key: str, match: Optional[str] = None, count: Optional[int] = None, *, encoding: Optional[Encoding] = None
+    ) -> ScanIter: ...
     async def eval(
         self, script: str, numkeys: int, *keys_and_args: Arg, encoding: Optional[Encoding] = None
     ) -> Result: ...
+    async def evalsha(
+        self, sha: str, numkeys: int, *keys_and_args: Arg, encoding: Optional[Encoding] = None
+    ) -> Result: ...
+    def register_script(self, source: str) -> Script: ...
+    async def script_exists(self, *shas: str) -> List[bool]: ...
+    async def script_flush(self, mode: Optional[str] = None) -> bool: ...
+    async def function_load(self, code: str, replace: bool = False) -> str: ...
+    async def function_list(
+        self, library: Optional[str] = None, withcode: bool = False, *, encoding: Optional[Encoding] = None
+    ) -> List[Dict[str, Any]]: ...
+    async def function_delete(self, library: str) -> bool: ...
+    async def fcall(
+        self, function: str, numkeys: int, *keys_and_args: Arg, encoding: Optional[Encoding] = None
+    ) -> Result: ...
+    async def fcall_ro(
+        self, function: str, numkeys: int, *keys_and_args: Arg, encoding: Optional[Encoding] = None
+    ) -> Result: ...
     @overload
     async def set(
         self,
         key: str,
-        value: Arg,
+        value: Any,
         ex: Optional[int] = None,
         encoding: Optional[Encoding] = None,
     ) -> Result: ...
@@ -31,23 +126,27 @@ class AsyncClient:
     async def set(
         self,
         key: str,
-        value: Arg,
+        value: Any,
         px: Optional[int] = None,
         encoding: Optional[Encoding] = None,
     ) -> Result: ...
     async def get(self, key: str, *, encoding: Optional[Encoding] = None) -> Result: ...
     @overload
-    async def hset(self, key: str, field: str, value: Arg, *pairs) -> int: ...
+    async def hset(
+        self, key: str, field: str, value: Any, *pairs, encoding: Optional[Encoding] = None
+    ) -> int: ...
     @overload
-    async def hset(self, key: str, mapping: Dict[str, Arg]) -> int: ...
+    async def hset(
+        self, key: str, mapping: Dict[str, Any], *, encoding: Optional[Encoding] = None
+    ) -> int: ...
     async def hget(self, key: str, field: str, *, encoding: Optional[Encoding] = None) -> Result: ...
     async def hmget(self, key: str, *fields: str, encoding: Optional[Encoding] = None) -> Result: ...
     async def hgetall(self, key: str, *, encoding: Optional[Encoding] = None) -> Dict: ...
     async def hdel(self, key: str, *fields: str) -> int: ...
     async def hexists(self, key: str, field: str) -> bool: ...
     async def incr(self, key: str, delta: Union[None, int, float] = None) -> float: ...
-    async def lpush(self, key: str, value: Arg) -> int: ...
-    async def rpush(self, key: str, value: Arg) -> int: ...
+    async def lpush(self, key: str, value: Any, *, encoding: Optional[Encoding] = None) -> int: ...
+    async def rpush(self, key: str, value: Any, *, encoding: Optional[Encoding] = None) -> int: ...
     async def lpop(self, key: str, *, count=None, encoding: Optional[Encoding] = None) -> Result: ...
     async def lrem(self, key: str, count: int, element: Arg) -> Result: ...
     async def blpop(self, *keys: str, timeout: float, encoding: Optional[Encoding] = None) -> Dict[str, Result]: ...
@@ -68,19 +167,20 @@ class AsyncClient:
         self,
         stream: str,
         id: str,
-        items: Dict[str, Arg],
+        items: Dict[str, Any],
         *,
         mkstream: bool = True,
         maxlen: Optional[int] = None,
         minid: Optional[int] = None,
         approx: bool = True,
         limit: Optional[int] = None,
+        encoding: Optional[Encoding] = None,
     ) -> str: ...
     @overload
     async def xadd(
         self,
         stream: str,
-        items: Dict[str, Arg],
+        items: Dict[str, Any],
         *,
         id: str = "*",
         mkstream: bool = True,
@@ -88,18 +188,20 @@ class AsyncClient:
         minid: Optional[int] = None,
         approx: bool = True,
         limit: Optional[int] = None,
+        encoding: Optional[Encoding] = None,
     ) -> str: ...
     @overload
     async def xadd(
         self,
         stream: str,
-        *args: Arg,
+        *args: Any,
         id: str = "*",
         mkstream: bool = True,
         maxlen: Optional[int] = None,
         minid: Optional[int] = None,
         approx: bool = True,
         limit: Optional[int] = None,
+        encoding: Optional[Encoding] = None,
     ) -> str: ...
     @overload
     async def xread(
@@ -111,8 +213,21 @@ class AsyncClient:
         noack: Optional[bool] = None,
         group: Optional[str] = None,
         encoding: Optional[Encoding] = None,
+        entries: Literal[False] = False,
     ) -> Dict: ...
     @overload
+    async def xread(
+        self,
+        streams: Dict[str, Union[str, Literal["$"], Literal[">"], Literal[0]]],
+        *,
+        block: Optional[int] = None,
+        count: Optional[int] = None,
+        noack: Optional[bool] = None,
+        group: Optional[str] = None,
+        encoding: Optional[Encoding] = None,
+        entries: Literal[True],
+    ) -> List[StreamEntry]: ...
+    @overload
     async def xread(
         self,
         *streams: str,
@@ -122,7 +237,20 @@ class AsyncClient:
         noack: Optional[bool] = None,
         group: Optional[str] = None,
         encoding: Optional[Encoding] = None,
+        entries: Literal[False] = False,
     ) -> Dict: ...
+    @overload
+    async def xread(
+        self,
+        *streams: str,
+        id: Union[None, str, Literal["$"], Literal[">"], Literal[0]] = None,
+        block: Optional[int] = None,
+        count: Optional[int] = None,
+        noack: Optional[bool] = None,
+        group: Optional[str] = None,
+        encoding: Optional[Encoding] = None,
+        entries: Literal[True],
+    ) -> List[StreamEntry]: ...
     async def xack(
         self,
         key: str,
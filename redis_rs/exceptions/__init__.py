@@ -1,3 +1,16 @@
 from ..redis_rs import exceptions
 
+PoolError = exceptions.PoolError
 RedisError = exceptions.RedisError
+WatchError = exceptions.WatchError
+TimeoutError = exceptions.TimeoutError
+ConnectionError = exceptions.ConnectionError
+AuthenticationError = exceptions.AuthenticationError
+ResponseError = exceptions.ResponseError
+WrongTypeError = exceptions.WrongTypeError
+NoScriptError = exceptions.NoScriptError
+BusyLoadingError = exceptions.BusyLoadingError
+ClusterDownError = exceptions.ClusterDownError
+MovedError = exceptions.MovedError
+AskError = exceptions.AskError
+ReadOnlyError = exceptions.ReadOnlyError
@@ -7,6 +7,10 @@ Encoding = Union[
     Literal["float"],
     Literal["info"],
     Literal["json"],
+    Literal["pickle"],
+    Literal["msgpack"],
+    # registered with redis_rs.register_codec
+    str,
 ]
 Arg = Union[str, bytes, int, float]
 Result = Union[bytes, str, int, float, dict, list]
@@ -0,0 +1,458 @@
+use std::{
+    collections::{hash_map, BTreeMap, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use redis::{AsyncConnectionConfig, Client, ConnectionInfo, ProtocolVersion, PushKind, Value};
+use tokio::sync::mpsc;
+
+use crate::{config, error};
+
+const MIN_DELAY: u64 = 100; // ms
+
+const CACHEABLE_COMMANDS: &[&[u8]] = &[b"GET", b"HGET", b"HGETALL"];
+
+type CacheKey = Vec<Vec<u8>>;
+
+struct Entry {
+    value: Value,
+    expires: Option<Instant>,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<CacheKey, Entry>,
+    // redis key -> cached commands reading it
+    keys: HashMap<Vec<u8>, HashSet<CacheKey>>,
+    // least recently used first
+    lru: BTreeMap<u64, CacheKey>,
+    tick: u64,
+}
+
+impl Inner {
+    fn remove(&mut self, cache_key: &CacheKey) -> Option<Entry> {
+        let entry = self.entries.remove(cache_key)?;
+        self.lru.remove(&entry.tick);
+        if let Some(key) = cache_key.get(1) {
+            if let Some(cached) = self.keys.get_mut(key) {
+                cached.remove(cache_key);
+                if cached.is_empty() {
+                    self.keys.remove(key);
+                }
+            }
+        }
+        Some(entry)
+    }
+
+    fn touch(&mut self, cache_key: &CacheKey) {
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(cache_key) {
+            self.lru.remove(&entry.tick);
+            entry.tick = self.tick;
+            self.lru.insert(self.tick, cache_key.clone());
+        }
+    }
+}
+
+/// Local cache of read replies invalidated by CLIENT TRACKING
+pub struct ClientCache {
+    max_entries: usize,
+    ttl: Option<Duration>,
+    inner: Mutex<Inner>,
+    // bumped on every invalidation to drop replies racing with it
+    epoch: AtomicU64,
+    enabled: AtomicBool,
+    // tracking connections not established yet
+    disconnected: AtomicUsize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl ClientCache {
+    pub fn new(options: &config::ClientCache) -> Self {
+        Self {
+            max_entries: options.max_entries,
+            ttl: options.ttl,
+            inner: Default::default(),
+            epoch: AtomicU64::new(0),
+            enabled: AtomicBool::new(false),
+            disconnected: AtomicUsize::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Key of the cache entry when the command reply may be cached
+    pub fn cache_key(cmd: &redis::Cmd) -> Option<CacheKey> {
+        let mut args = vec![];
+        for arg in cmd.args_iter() {
+            match arg {
+                redis::Arg::Simple(arg) => args.push(arg.to_vec()),
+                _ => return None,
+            }
+        }
+        let name = args.first_mut()?;
+        name.make_ascii_uppercase();
+        (CACHEABLE_COMMANDS.contains(&name.as_slice()) && args.len() > 1).then_some(args)
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    pub fn get(&self, cache_key: &CacheKey) -> Option<Value> {
+        if !self.is_active() {
+            return None;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let expired = match inner.entries.get(cache_key) {
+            Some(entry) => entry.expires.is_some_and(|e| e <= Instant::now()),
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+        };
+        if expired {
+            inner.remove(cache_key);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        inner.touch(cache_key);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        inner
+            .entries
+            .get(cache_key)
+            .map(|entry| entry.value.clone())
+    }
+
+    /// Store a reply fetched when the cache was at `epoch`
+    pub fn insert(&self, cache_key: CacheKey, value: Value, epoch: u64) {
+        if !self.is_active() || matches!(value, Value::ServerError(_)) {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        if self.epoch() != epoch {
+            return;
+        }
+        inner.remove(&cache_key);
+        while inner.entries.len() >= self.max_entries {
+            let Some((_, oldest)) = inner.lru.pop_first() else {
+                break;
+            };
+            inner.remove(&oldest);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+        if self.max_entries == 0 {
+            return;
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.lru.insert(tick, cache_key.clone());
+        inner
+            .keys
+            .entry(cache_key[1].clone())
+            .or_default()
+            .insert(cache_key.clone());
+        let expires = self.ttl.map(|ttl| Instant::now() + ttl);
+        inner.entries.insert(
+            cache_key,
+            Entry {
+                value,
+                expires,
+                tick,
+            },
+        );
+    }
+
+    pub fn invalidate(&self, keys: &[Vec<u8>]) {
+        let mut inner = self.inner.lock().unwrap();
+        self.epoch.fetch_add(1, Ordering::AcqRel);
+        for key in keys {
+            for cache_key in inner.keys.remove(key).unwrap_or_default() {
+                inner.remove(&cache_key);
+            }
+        }
+    }
+
+    pub fn flush(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        self.epoch.fetch_add(1, Ordering::AcqRel);
+        *inner = Inner::default();
+    }
+
+    fn is_active(&self) -> bool {
+        self.enabled.load(Ordering::Acquire) && self.disconnected.load(Ordering::Acquire) == 0
+    }
+
+    /// Start serving replies once invalidations are delivered
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Release);
+    }
+
+    pub fn status(&self) -> Value {
+        let size = self.inner.lock().unwrap().entries.len();
+        let counter = |name: &str, value: u64| {
+            (
+                Value::SimpleString(name.to_string()),
+                Value::Int(value as i64),
+            )
+        };
+        Value::Map(vec![
+            counter("hits", self.hits.load(Ordering::Relaxed)),
+            counter("misses", self.misses.load(Ordering::Relaxed)),
+            counter("evictions", self.evictions.load(Ordering::Relaxed)),
+            counter("size", size as u64),
+            counter("max_entries", self.max_entries as u64),
+        ])
+    }
+}
+
+/// Stops serving replies while alive, invalidations may be lost in the meantime
+struct Paused(Arc<ClientCache>);
+
+impl Paused {
+    fn new(cache: Arc<ClientCache>) -> Self {
+        cache.disconnected.fetch_add(1, Ordering::AcqRel);
+        cache.flush();
+        Self(cache)
+    }
+}
+
+impl Drop for Paused {
+    fn drop(&mut self) {
+        self.0.flush();
+        self.0.disconnected.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+async fn connect(
+    client: &Client,
+    tx: mpsc::UnboundedSender<redis::PushInfo>,
+) -> redis::RedisResult<redis::aio::MultiplexedConnection> {
+    let config = AsyncConnectionConfig::new().set_push_sender(tx);
+    let mut conn = client
+        .get_multiplexed_async_connection_with_config(&config)
+        .await?;
+    redis::cmd("CLIENT")
+        .arg("TRACKING")
+        .arg("ON")
+        .arg("BCAST")
+        .exec_async(&mut conn)
+        .await?;
+    Ok(conn)
+}
+
+/// Keep a RESP3 connection in broadcasting tracking mode and apply its invalidations
+pub async fn track(
+    cache: Arc<ClientCache>,
+    info: ConnectionInfo,
+    max_delay: Option<u64>,
+) -> Result<tokio::task::JoinHandle<()>, error::RedisError> {
+    let redis_settings = info
+        .redis_settings()
+        .clone()
+        .set_protocol(ProtocolVersion::RESP3);
+    let client = Client::open(info.set_redis_settings(redis_settings))?;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut conn = connect(&client, tx.clone()).await?;
+    let max_delay = Duration::from_millis(max_delay.unwrap_or(10_000).max(MIN_DELAY));
+    Ok(tokio::spawn(async move {
+        while let Some(push) = rx.recv().await {
+            match push.kind {
+                PushKind::Invalidate => match push.data.into_iter().next() {
+                    Some(Value::Array(keys)) => {
+                        let keys: Vec<Vec<u8>> = keys
+                            .into_iter()
+                            .filter_map(|key| redis::FromRedisValue::from_redis_value(key).ok())
+                            .collect();
+                        cache.invalidate(&keys);
+                    }
+                    // FLUSHDB/FLUSHALL invalidate everything
+                    _ => cache.flush(),
+                },
+                PushKind::Disconnection => {
+                    let paused = Paused::new(cache.clone());
+                    let mut delay = Duration::from_millis(MIN_DELAY);
+                    conn = loop {
+                        tokio::time::sleep(delay).await;
+                        match connect(&client, tx.clone()).await {
+                            Ok(conn) => break conn,
+                            Err(_) => delay = (delay * 2).min(max_delay),
+                        }
+                    };
+                    drop(paused);
+                }
+                _ => continue,
+            }
+        }
+        drop(conn);
+    }))
+}
+
+/// Tracking connections of the masters, closed on drop
+#[derive(Default)]
+pub struct Tracking {
+    tasks: HashMap<String, tokio::task::JoinHandle<()>>,
+    // set until every master of the last update is tracked
+    paused: Option<Paused>,
+}
+
+impl Tracking {
+    /// Track the given masters, on a change the cache is flushed and serves
+    /// no replies until all new masters are tracked
+    pub async fn update(
+        &mut self,
+        cache: &Arc<ClientCache>,
+        config: &config::Config,
+        masters: Vec<String>,
+    ) -> Result<(), error::RedisError> {
+        let masters: HashSet<String> = masters.into_iter().collect();
+        let changed = masters.len() != self.tasks.len()
+            || !masters.iter().all(|addr| self.tasks.contains_key(addr));
+        if !changed && self.paused.is_none() {
+            return Ok(());
+        }
+        self.paused
+            .get_or_insert_with(|| Paused::new(cache.clone()));
+        self.tasks.retain(|addr, task| {
+            let keep = masters.contains(addr);
+            if !keep {
+                task.abort();
+            }
+            keep
+        });
+        for addr in masters {
+            if let hash_map::Entry::Vacant(entry) = self.tasks.entry(addr) {
+                let info = config.node_info(entry.key())?;
+                entry.insert(track(cache.clone(), info, config.max_delay).await?);
+            }
+        }
+        self.paused = None;
+        Ok(())
+    }
+}
+
+impl Drop for Tracking {
+    fn drop(&mut self) {
+        for task in self.tasks.values() {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use redis::Value;
+
+    use crate::{config, fake};
+
+    use super::{ClientCache, Tracking};
+
+    fn cache(max_entries: usize) -> ClientCache {
+        let cache = ClientCache::new(&config::ClientCache {
+            max_entries,
+            ttl: None,
+        });
+        cache.enable();
+        cache
+    }
+
+    fn get(key: &str) -> Vec<Vec<u8>> {
+        ClientCache::cache_key(&redis::cmd("GET").arg(key).to_owned()).unwrap()
+    }
+
+    #[test]
+    fn cache_key() {
+        assert!(ClientCache::cache_key(&redis::cmd("SET").arg("a").arg(1).to_owned()).is_none());
+        assert!(ClientCache::cache_key(&redis::cmd("GET").to_owned()).is_none());
+        let key = ClientCache::cache_key(&redis::cmd("hget").arg("h").arg("f").to_owned());
+        assert_eq!(
+            key,
+            Some(vec![b"HGET".to_vec(), b"h".to_vec(), b"f".to_vec()])
+        );
+    }
+
+    #[test]
+    fn lru_eviction() {
+        let cache = cache(2);
+        let one = Value::BulkString(b"1".to_vec());
+        cache.insert(get("a"), one.clone(), cache.epoch());
+        cache.insert(get("b"), one.clone(), cache.epoch());
+        assert_eq!(cache.get(&get("a")), Some(one.clone()));
+        cache.insert(get("c"), one.clone(), cache.epoch());
+        assert_eq!(cache.get(&get("b")), None);
+        assert_eq!(cache.get(&get("a")), Some(one.clone()));
+        assert_eq!(cache.evictions.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn invalidate() {
+        let cache = cache(10);
+        let one = Value::BulkString(b"1".to_vec());
+        let epoch = cache.epoch();
+        cache.insert(get("a"), one.clone(), epoch);
+        cache.invalidate(&[b"a".to_vec()]);
+        assert_eq!(cache.get(&get("a")), None);
+        // reply fetched before the invalidation is not stored
+        cache.insert(get("a"), one, epoch);
+        assert_eq!(cache.get(&get("a")), None);
+    }
+
+    #[tokio::test]
+    async fn tracking_masters() {
+        let tracked = Arc::new(AtomicUsize::new(0));
+        let handler = {
+            let tracked = tracked.clone();
+            move |cmd: &[Vec<u8>]| match (
+                fake::name(cmd).as_str(),
+                cmd.get(1).map(|a| a.as_slice()),
+            ) {
+                ("HELLO", _) => b"%0\r\n".to_vec(),
+                ("CLIENT", Some(b"TRACKING")) => {
+                    tracked.fetch_add(1, Ordering::Relaxed);
+                    fake::ok()
+                }
+                _ => fake::ok(),
+            }
+        };
+        let a = fake::serve(handler.clone()).await;
+        let b = fake::serve(handler).await;
+        let cache = Arc::new(cache(10));
+        let config = config::Config::default();
+        let mut tracking = Tracking::default();
+        tracking
+            .update(&cache, &config, vec![a.clone()])
+            .await
+            .unwrap();
+        tracking
+            .update(&cache, &config, vec![a.clone()])
+            .await
+            .unwrap();
+        assert_eq!(tracked.load(Ordering::Relaxed), 1);
+        assert!(cache.is_active());
+
+        // the cache is paused until a new master is tracked
+        let masters = vec![a.clone(), "127.0.0.1:1".to_string()];
+        assert!(tracking.update(&cache, &config, masters).await.is_err());
+        assert!(!cache.is_active());
+        tracking
+            .update(&cache, &config, vec![b.clone()])
+            .await
+            .unwrap();
+        assert_eq!(tracked.load(Ordering::Relaxed), 2);
+        assert_eq!(tracking.tasks.keys().collect::<Vec<_>>(), [&b]);
+        assert!(cache.is_active());
+    }
+}
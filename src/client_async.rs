@@ -1,7 +1,23 @@
-use crate::{client_result_async::AsyncClientResult, command::Params, types};
-use pyo3::prelude::*;
+use crate::{
+    client_result_async::AsyncClientResult,
+    command::Params,
+    error,
+    hooks::PyHook,
+    metrics,
+    pipeline::Pipeline,
+    pubsub::Subscription,
+    scan::{Kind, ScanIter},
+    script::Script,
+    transaction::Transaction,
+    types,
+};
+use pyo3::{prelude::*, types::PyDict, IntoPyObjectExt};
 use redis::streams::StreamReadOptions;
-use std::{collections::HashMap, num::NonZeroUsize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    num::NonZeroUsize,
+    sync::Arc,
+};
 
 #[pyclass(from_py_object)]
 #[derive(Clone)]
@@ -36,76 +52,183 @@ impl Client {
         Ok(result)
     }
 
-    #[pyo3(signature = (cmd, *args, encoding = None))]
+    fn metrics(&self, py: Python) -> PyResult<Py<PyAny>> {
+        let result = PyDict::new(py);
+        for ((command, node), s) in self.cr.metrics()?.snapshot() {
+            let buckets = PyDict::new(py);
+            let bounds = metrics::BUCKETS.iter().chain([f64::INFINITY].iter());
+            for (le, n) in bounds.zip(s.cumulative()) {
+                buckets.set_item(le, n)?;
+            }
+            let series = PyDict::new(py);
+            series.set_item("count", s.count)?;
+            series.set_item("errors", s.errors)?;
+            series.set_item("sum", s.sum)?;
+            series.set_item("buckets", buckets)?;
+            let nodes = match result.get_item(&command)? {
+                Some(nodes) => nodes.cast_into::<PyDict>()?,
+                None => {
+                    let nodes = PyDict::new(py);
+                    result.set_item(&command, &nodes)?;
+                    nodes
+                }
+            };
+            nodes.set_item(node, series)?;
+        }
+        result.into_py_any(py)
+    }
+
+    fn metrics_prometheus(&self) -> PyResult<String> {
+        Ok(self.cr.metrics()?.prometheus())
+    }
+
+    fn add_hook(&self, py: Python, hook: Py<PyAny>) -> PyResult<()> {
+        self.cr.hooks()?.add(Arc::new(PyHook::new(py, hook)?));
+        Ok(())
+    }
+
+    fn remove_hook(&self, py: Python, hook: Py<PyAny>) -> PyResult<bool> {
+        Ok(self.cr.hooks()?.remove(py, &hook))
+    }
+
+    #[pyo3(signature = (cmd, *args, encoding = None, timeout = None))]
     async fn execute(
         &self,
         cmd: types::Str,
         args: Vec<types::Arg>,
         encoding: Option<String>,
+        timeout: Option<f64>,
     ) -> PyResult<Py<PyAny>> {
         let cmd = String::from(cmd).to_ascii_uppercase();
         let cmd = redis::cmd(cmd.as_str()).arg(args).to_owned();
-        let mut params = Params::from(&cmd);
+        let mut params = self.cr.params(&cmd);
         params.codec = encoding.into();
+        params.timeout = timeout.map(|t| types::duration("timeout", t)).transpose()?;
         self.cr.execute(cmd, params).await
     }
 
-    #[pyo3(signature = (cmd, *args))]
-    async fn fetch_str(&self, cmd: types::Str, args: Vec<types::Arg>) -> PyResult<Option<String>> {
+    fn pipeline(&self) -> Pipeline {
+        Pipeline::new(self.cr.clone())
+    }
+
+    #[pyo3(signature = (*watch, retries = 0))]
+    fn transaction(&self, watch: Vec<types::Str>, retries: usize) -> Transaction {
+        let watch = watch.iter().map(Vec::from).collect();
+        Transaction::new(self.cr.clone(), watch, retries)
+    }
+
+    #[pyo3(signature = (*channels, encoding = None))]
+    async fn subscribe(
+        &self,
+        channels: Vec<types::Str>,
+        encoding: Option<String>,
+    ) -> PyResult<Subscription> {
+        let channels = channels.iter().map(Vec::from).collect();
+        let rx = self.cr.subscribe(channels, vec![]).await?;
+        Ok(Subscription::new(rx, encoding.into()))
+    }
+
+    #[pyo3(signature = (*patterns, encoding = None))]
+    async fn psubscribe(
+        &self,
+        patterns: Vec<types::Str>,
+        encoding: Option<String>,
+    ) -> PyResult<Subscription> {
+        let patterns = patterns.iter().map(Vec::from).collect();
+        let rx = self.cr.subscribe(vec![], patterns).await?;
+        Ok(Subscription::new(rx, encoding.into()))
+    }
+
+    #[pyo3(signature = (channel, message))]
+    async fn publish(&self, channel: types::Str, message: types::Arg) -> PyResult<i64> {
+        let cmd = redis::cmd("PUBLISH").arg(channel).arg(message).to_owned();
+        self.cr.fetch(cmd, Params::default()).await
+    }
+
+    #[pyo3(signature = (cmd, *args, timeout = None))]
+    async fn fetch_str(
+        &self,
+        cmd: types::Str,
+        args: Vec<types::Arg>,
+        timeout: Option<f64>,
+    ) -> PyResult<Option<String>> {
         let cmd = String::from(cmd).to_ascii_uppercase();
         let cmd = redis::cmd(cmd.as_str()).arg(args).to_owned();
-        let params = Params::from(&cmd);
+        let mut params = self.cr.params(&cmd);
+        params.timeout = timeout.map(|t| types::duration("timeout", t)).transpose()?;
         self.cr.fetch(cmd, params).await
     }
 
-    #[pyo3(signature = (cmd, *args))]
-    async fn fetch_bytes(&self, cmd: types::Str, args: Vec<types::Arg>) -> PyResult<Vec<u8>> {
+    #[pyo3(signature = (cmd, *args, timeout = None))]
+    async fn fetch_bytes(
+        &self,
+        cmd: types::Str,
+        args: Vec<types::Arg>,
+        timeout: Option<f64>,
+    ) -> PyResult<Vec<u8>> {
         let cmd = String::from(cmd).to_ascii_uppercase();
         let cmd = redis::cmd(cmd.as_str()).arg(args).to_owned();
-        let params = Params::from(&cmd);
+        let mut params = self.cr.params(&cmd);
+        params.timeout = timeout.map(|t| types::duration("timeout", t)).transpose()?;
         self.cr.fetch(cmd, params).await
     }
 
-    #[pyo3(signature = (cmd, *args))]
-    async fn fetch_list(&self, cmd: types::Str, args: Vec<types::Arg>) -> PyResult<Vec<String>> {
+    #[pyo3(signature = (cmd, *args, timeout = None))]
+    async fn fetch_list(
+        &self,
+        cmd: types::Str,
+        args: Vec<types::Arg>,
+        timeout: Option<f64>,
+    ) -> PyResult<Vec<String>> {
         let cmd = String::from(cmd).to_ascii_uppercase();
         let cmd = redis::cmd(cmd.as_str()).arg(args).to_owned();
-        let params = Params::from(&cmd);
+        let mut params = self.cr.params(&cmd);
+        params.timeout = timeout.map(|t| types::duration("timeout", t)).transpose()?;
         self.cr.fetch(cmd, params).await
     }
 
-    #[pyo3(signature = (cmd, *args, encoding = None))]
+    #[pyo3(signature = (cmd, *args, encoding = None, timeout = None))]
     async fn fetch_dict(
         &self,
         cmd: types::Str,
         args: Vec<types::Arg>,
         encoding: Option<String>,
+        timeout: Option<f64>,
     ) -> PyResult<Py<PyAny>> {
         let cmd = String::from(cmd).to_ascii_uppercase();
         let cmd = redis::cmd(cmd.as_str()).arg(args).to_owned();
-        let mut params = Params::from(&cmd);
+        let mut params = self.cr.params(&cmd);
         params.codec = encoding.into();
+        params.timeout = timeout.map(|t| types::duration("timeout", t)).transpose()?;
         self.cr.fetch_dict(cmd, params).await
     }
 
-    #[pyo3(signature = (cmd, *args))]
+    #[pyo3(signature = (cmd, *args, timeout = None))]
     async fn fetch_scores(
         &self,
         cmd: types::Str,
         args: Vec<types::Arg>,
+        timeout: Option<f64>,
     ) -> PyResult<HashMap<String, f64>> {
         let cmd = String::from(cmd).to_ascii_uppercase();
         let cmd = redis::cmd(cmd.as_str()).arg(args).to_owned();
-        let mut params = Params::from(&cmd);
+        let mut params = self.cr.params(&cmd);
         params.codec = types::Codec::Float;
+        params.timeout = timeout.map(|t| types::duration("timeout", t)).transpose()?;
         self.cr.fetch(cmd, params).await
     }
 
-    #[pyo3(signature = (cmd, *args))]
-    async fn fetch_int(&self, cmd: types::Str, args: Vec<types::Arg>) -> PyResult<i64> {
+    #[pyo3(signature = (cmd, *args, timeout = None))]
+    async fn fetch_int(
+        &self,
+        cmd: types::Str,
+        args: Vec<types::Arg>,
+        timeout: Option<f64>,
+    ) -> PyResult<i64> {
         let cmd = String::from(cmd).to_ascii_uppercase();
         let cmd = redis::cmd(cmd.as_str()).arg(args).to_owned();
-        let params = Params::from(&cmd);
+        let mut params = self.cr.params(&cmd);
+        params.timeout = timeout.map(|t| types::duration("timeout", t)).transpose()?;
         self.cr.fetch(cmd, params).await
     }
 
@@ -146,6 +269,54 @@ impl Client {
         self.cr.fetch(cmd, params).await
     }
 
+    #[pyo3(signature = (r#match = None, count = None, r#type = None, *, encoding = None))]
+    fn scan_iter(
+        &self,
+        r#match: Option<types::Str>,
+        count: Option<usize>,
+        r#type: Option<types::Str>,
+        encoding: Option<String>,
+    ) -> ScanIter {
+        let (cr, codec) = (self.cr.clone(), encoding.into());
+        ScanIter::new(cr, Kind::Keys, None, r#match, count, r#type, codec)
+    }
+
+    #[pyo3(signature = (key, r#match = None, count = None, *, encoding = None))]
+    fn sscan_iter(
+        &self,
+        key: types::Str,
+        r#match: Option<types::Str>,
+        count: Option<usize>,
+        encoding: Option<String>,
+    ) -> ScanIter {
+        let (cr, key, codec) = (self.cr.clone(), Some(Vec::from(&key)), encoding.into());
+        ScanIter::new(cr, Kind::Members, key, r#match, count, None, codec)
+    }
+
+    #[pyo3(signature = (key, r#match = None, count = None, *, encoding = None))]
+    fn hscan_iter(
+        &self,
+        key: types::Str,
+        r#match: Option<types::Str>,
+        count: Option<usize>,
+        encoding: Option<String>,
+    ) -> ScanIter {
+        let (cr, key, codec) = (self.cr.clone(), Some(Vec::from(&key)), encoding.into());
+        ScanIter::new(cr, Kind::Fields, key, r#match, count, None, codec)
+    }
+
+    #[pyo3(signature = (key, r#match = None, count = None, *, encoding = None))]
+    fn zscan_iter(
+        &self,
+        key: types::Str,
+        r#match: Option<types::Str>,
+        count: Option<usize>,
+        encoding: Option<String>,
+    ) -> ScanIter {
+        let (cr, key, codec) = (self.cr.clone(), Some(Vec::from(&key)), encoding.into());
+        ScanIter::new(cr, Kind::Scores, key, r#match, count, None, codec)
+    }
+
     #[pyo3(signature = (script, numkeys, *args, encoding = None))]
     async fn eval(
         &self,
@@ -165,6 +336,145 @@ impl Client {
         self.cr.execute(cmd, params).await
     }
 
+    #[pyo3(signature = (sha, numkeys, *args, encoding = None))]
+    async fn evalsha(
+        &self,
+        sha: types::Str,
+        numkeys: u8,
+        args: Vec<types::Arg>,
+        encoding: Option<String>,
+    ) -> PyResult<Py<PyAny>> {
+        let cmd = redis::cmd("EVALSHA")
+            .arg(sha)
+            .arg(numkeys)
+            .arg(args)
+            .to_owned();
+        let mut params = self.cr.params(&cmd);
+        params.codec = encoding.into();
+        params.block = true;
+        self.cr.execute(cmd, params).await
+    }
+
+    #[pyo3(signature = (source))]
+    fn register_script(&self, source: String) -> Script {
+        Script::new(self.cr.clone(), source)
+    }
+
+    #[pyo3(signature = (*shas))]
+    async fn script_exists(&self, shas: Vec<types::Str>) -> PyResult<Vec<bool>> {
+        let cmd = redis::cmd("SCRIPT").arg("EXISTS").arg(shas).to_owned();
+        let params = self.cr.params(&cmd);
+        self.cr.fetch(cmd, params).await
+    }
+
+    #[pyo3(signature = (mode = None))]
+    async fn script_flush(&self, mode: Option<types::Str>) -> PyResult<bool> {
+        let cmd = redis::cmd("SCRIPT").arg("FLUSH").arg(mode).to_owned();
+        let params = self.cr.params(&cmd);
+        self.cr.fetch(cmd, params).await
+    }
+
+    #[pyo3(signature = (code, replace = false))]
+    async fn function_load(&self, code: types::Str, replace: bool) -> PyResult<String> {
+        let mut cmd = redis::cmd("FUNCTION");
+        cmd.arg("LOAD");
+        if replace {
+            cmd.arg("REPLACE");
+        }
+        cmd.arg(code);
+        self.cr.fetch(cmd, Params::default()).await
+    }
+
+    #[pyo3(signature = (library = None, withcode = false, *, encoding = None))]
+    async fn function_list(
+        &self,
+        library: Option<types::Str>,
+        withcode: bool,
+        encoding: Option<String>,
+    ) -> PyResult<Py<PyAny>> {
+        let mut cmd = redis::cmd("FUNCTION");
+        cmd.arg("LIST");
+        if let Some(library) = library {
+            cmd.arg("LIBRARYNAME").arg(library);
+        }
+        if withcode {
+            cmd.arg("WITHCODE");
+        }
+        let libraries: Vec<BTreeMap<String, redis::Value>> =
+            self.cr.fetch(cmd, Params::default()).await?;
+        let codec = types::Codec::from(encoding);
+        Python::attach(|py| {
+            let mut result = vec![];
+            for library in libraries {
+                let dict = PyDict::new(py);
+                for (k, v) in library {
+                    if k == "functions" {
+                        let functions: Vec<BTreeMap<String, redis::Value>> =
+                            redis::FromRedisValue::from_redis_value(v)
+                                .map_err(|e| error::RedisError::RedisError(e.into()))?;
+                        let mut items = vec![];
+                        for function in functions {
+                            let item = PyDict::new(py);
+                            for (k, v) in function {
+                                item.set_item(k, types::to_object(py, v, codec.clone())?)?;
+                            }
+                            items.push(item);
+                        }
+                        dict.set_item(k, items)?;
+                    } else {
+                        dict.set_item(k, types::to_object(py, v, codec.clone())?)?;
+                    }
+                }
+                result.push(dict);
+            }
+            result.into_py_any(py)
+        })
+    }
+
+    #[pyo3(signature = (library))]
+    async fn function_delete(&self, library: types::Str) -> PyResult<bool> {
+        let cmd = redis::cmd("FUNCTION").arg("DELETE").arg(library).to_owned();
+        self.cr.fetch(cmd, Params::default()).await
+    }
+
+    #[pyo3(signature = (function, numkeys, *args, encoding = None))]
+    async fn fcall(
+        &self,
+        function: types::Str,
+        numkeys: u8,
+        args: Vec<types::Arg>,
+        encoding: Option<String>,
+    ) -> PyResult<Py<PyAny>> {
+        let cmd = redis::cmd("FCALL")
+            .arg(function)
+            .arg(numkeys)
+            .arg(args)
+            .to_owned();
+        let mut params = self.cr.params(&cmd);
+        params.codec = encoding.into();
+        params.block = true;
+        self.cr.execute(cmd, params).await
+    }
+
+    #[pyo3(signature = (function, numkeys, *args, encoding = None))]
+    async fn fcall_ro(
+        &self,
+        function: types::Str,
+        numkeys: u8,
+        args: Vec<types::Arg>,
+        encoding: Option<String>,
+    ) -> PyResult<Py<PyAny>> {
+        let cmd = redis::cmd("FCALL_RO")
+            .arg(function)
+            .arg(numkeys)
+            .arg(args)
+            .to_owned();
+        let mut params = self.cr.params(&cmd);
+        params.codec = encoding.into();
+        params.block = true;
+        self.cr.execute(cmd, params).await
+    }
+
     #[pyo3(signature = (
         key,
         value,
@@ -176,13 +486,14 @@ impl Client {
     async fn set(
         &self,
         key: types::Str,
-        value: types::Arg,
+        value: Py<PyAny>,
         ex: Option<usize>,
         px: Option<usize>,
         encoding: Option<String>,
     ) -> PyResult<Py<PyAny>> {
         let mut params = Params::from(&key);
         params.codec = encoding.into();
+        let value = Python::attach(|py| types::encode(value.bind(py), &params.codec))?;
         let mut cmd = redis::cmd("SET").arg(key).arg(value).to_owned();
         if let Some(ex) = ex {
             cmd.arg(b"EX");
@@ -203,19 +514,21 @@ impl Client {
         self.cr.execute(cmd, params).await
     }
 
-    #[pyo3(signature = (key, *pairs, mapping = None))]
+    #[pyo3(signature = (key, *pairs, mapping = None, encoding = None))]
     async fn hset(
         &self,
         key: types::Str,
-        pairs: Vec<types::ScalarOrMap>,
-        mapping: Option<types::ScalarOrMap>,
+        pairs: Vec<Py<PyAny>>,
+        mapping: Option<Py<PyAny>>,
+        encoding: Option<String>,
     ) -> PyResult<i64> {
         let params = Params::from(&key);
-        let cmd = redis::cmd("HSET")
-            .arg(key)
-            .arg(pairs)
-            .arg(mapping)
-            .to_owned();
+        let codec = encoding.into();
+        let pairs = Python::attach(|py| {
+            let args = pairs.iter().chain(mapping.iter());
+            types::encode_pairs(args.map(|a| a.bind(py).clone()), &codec)
+        })?;
+        let cmd = redis::cmd("HSET").arg(key).arg(pairs).to_owned();
         self.cr.fetch(cmd, params).await
     }
 
@@ -278,16 +591,30 @@ impl Client {
         self.cr.fetch(cmd, params).await
     }
 
-    #[pyo3(signature = (key, value))]
-    async fn lpush(&self, key: types::Str, value: types::Arg) -> PyResult<i64> {
+    #[pyo3(signature = (key, value, *, encoding = None))]
+    async fn lpush(
+        &self,
+        key: types::Str,
+        value: Py<PyAny>,
+        encoding: Option<String>,
+    ) -> PyResult<i64> {
         let params = Params::from(&key);
+        let codec = encoding.into();
+        let value = Python::attach(|py| types::encode(value.bind(py), &codec))?;
         let cmd = redis::cmd("LPUSH").arg(key).arg(value).to_owned();
         self.cr.fetch(cmd, params).await
     }
 
-    #[pyo3(signature = (key, value))]
-    async fn rpush(&self, key: types::Str, value: types::Arg) -> PyResult<i64> {
+    #[pyo3(signature = (key, value, *, encoding = None))]
+    async fn rpush(
+        &self,
+        key: types::Str,
+        value: Py<PyAny>,
+        encoding: Option<String>,
+    ) -> PyResult<i64> {
         let params = Params::from(&key);
+        let codec = encoding.into();
+        let value = Python::attach(|py| types::encode(value.bind(py), &codec))?;
         let cmd = redis::cmd("RPUSH").arg(key).arg(value).to_owned();
         self.cr.fetch(cmd, params).await
     }
@@ -390,18 +717,20 @@ impl Client {
         minid = None,
         approx = true,
         limit = None,
+        encoding = None,
     ))]
     async fn xadd(
         &self,
         stream: types::Str,
-        mut args: Vec<types::ScalarOrMap>,
+        args: Vec<Py<PyAny>>,
         id: Option<types::Str>,
-        items: Option<HashMap<String, types::Arg>>,
+        items: Option<Py<PyAny>>,
         mkstream: bool,
         maxlen: Option<usize>,
         minid: Option<usize>,
         approx: bool,
         limit: Option<usize>,
+        encoding: Option<String>,
     ) -> PyResult<Option<String>> {
         let params = Params::from(&stream);
 
@@ -425,38 +754,34 @@ impl Client {
             cmd.arg("LIMIT").arg(limit);
         }
 
-        if let Some(id) = id {
-            cmd.arg(id);
-        } else if args.is_empty() {
-            cmd.arg(b"*");
-        } else {
-            match args.remove(0) {
-                types::ScalarOrMap::Map(m) => {
-                    cmd.arg(b"*");
-                    cmd.arg(m);
-                }
-                types::ScalarOrMap::BMap(m) => {
-                    cmd.arg(b"*");
-                    cmd.arg(m);
-                }
-                types::ScalarOrMap::Scalar(arg) => {
-                    if let Ok(id) = arg.to_normalized_stream_msg_id() {
-                        cmd.arg(id);
-                    } else {
-                        cmd.arg(b"*");
-                        cmd.arg(arg);
+        let codec = encoding.into();
+        let (id, fields) = Python::attach(|py| {
+            let mut args: Vec<_> = args.iter().map(|a| a.bind(py).clone()).collect();
+            let id = match id {
+                Some(id) => Vec::from(&id),
+                // the first scalar is the id when it looks like one
+                None => match args
+                    .first()
+                    .and_then(|a| a.extract::<types::Arg>().ok())
+                    .and_then(|a| a.to_normalized_stream_msg_id().ok())
+                {
+                    Some(id) => {
+                        args.remove(0);
+                        id.into_bytes()
                     }
-                }
-            }
-        };
-
-        cmd.arg(args).arg(items);
+                    None => b"*".to_vec(),
+                },
+            };
+            let args = args.into_iter().chain(items.map(|i| i.into_bound(py)));
+            Ok::<_, PyErr>((id, types::encode_pairs(args, &codec)?))
+        })?;
+        cmd.arg(id).arg(fields);
 
         self.cr.fetch(cmd, params).await
     }
 
     #[allow(clippy::too_many_arguments)]
-    #[pyo3(signature = (streams, *args, id=None, block=None, count=None, noack=None, group=None, encoding=None))]
+    #[pyo3(signature = (streams, *args, id=None, block=None, count=None, noack=None, group=None, encoding=None, entries=false))]
     async fn xread(
         &self,
         streams: types::ScalarOrMap,
@@ -467,6 +792,7 @@ impl Client {
         noack: Option<bool>,
         group: Option<types::Str>,
         encoding: Option<String>,
+        entries: bool,
     ) -> PyResult<Py<PyAny>> {
         let encoding = types::Codec::from(encoding);
         let mut params = Params::from(encoding);
@@ -515,7 +841,11 @@ impl Client {
         }
         params.keys = keys.iter().map(|k| k.as_bytes().to_vec()).collect();
         cmd.arg(options).arg("STREAMS").arg(keys).arg(ids);
-        self.cr.fetch_dict(cmd, params).await
+        if entries {
+            self.cr.fetch_entries(cmd, params).await
+        } else {
+            self.cr.fetch_dict(cmd, params).await
+        }
     }
 
     #[pyo3(signature = (key, group, *id))]
@@ -1,22 +1,31 @@
 use std::{collections::HashMap, sync::Arc};
 
 use pyo3::{prelude::*, IntoPyObjectExt};
-use redis::{Cmd, FromRedisValue, Value};
+use redis::{Cmd, ErrorKind, FromRedisValue, ServerErrorKind, Value};
 
-use crate::{client_async::Client, command::Params, error, pool_manager::PoolManager, types};
+use crate::{
+    client_async::Client, command::Params, error, hooks::Hooks, key_spec::KeySpecs,
+    metrics::Metrics, pool::Connection, pool_manager::PoolManager, pubsub, stream, types,
+};
 
 #[derive(Clone)]
 pub struct AsyncClientResult {
     pub(crate) cm: Arc<tokio::sync::RwLock<PoolManager>>,
+    pub(crate) key_specs: Arc<KeySpecs>,
 }
 
-fn tokio_rt() -> &'static tokio::runtime::Runtime {
+pub(crate) fn tokio_rt() -> &'static tokio::runtime::Runtime {
     use std::sync::OnceLock;
     static RT: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
     RT.get_or_init(|| tokio::runtime::Runtime::new().unwrap())
 }
 
 impl AsyncClientResult {
+    /// Keys and blocking flag of the command from the key specs of the server
+    pub fn params(&self, cmd: &Cmd) -> Params {
+        Params::with_specs(cmd, &self.key_specs)
+    }
+
     pub async fn init(&self, client: &Client) -> PyResult<Client> {
         let cm = self.cm.clone();
         tokio_rt()
@@ -41,22 +50,130 @@ impl AsyncClientResult {
         Ok(result)
     }
 
+    pub fn metrics(&self) -> Result<Arc<Metrics>, error::RedisError> {
+        let cm = self.cm.try_read()?;
+        Ok(cm.metrics.clone())
+    }
+
+    pub fn hooks(&self) -> Result<Arc<Hooks>, error::RedisError> {
+        let cm = self.cm.try_read()?;
+        Ok(cm.hooks.clone())
+    }
+
     pub async fn execute(&self, cmd: Cmd, params: Params) -> PyResult<Py<PyAny>> {
         let cm = self.cm.clone();
         let encoding = params.codec.clone();
         let result = tokio_rt()
-            .spawn(async move { cm.read().await.pool.execute(cmd, params).await })
+            .spawn(async move { cm.read().await.query(cmd, params).await })
             .await
             .unwrap()?;
         Python::attach(|py| types::to_object(py, result, encoding))
     }
 
+    /// Execute EVALSHA, loading the script on the node owning the keys on NOSCRIPT
+    pub async fn execute_script(
+        &self,
+        source: String,
+        cmd: Cmd,
+        params: Params,
+    ) -> PyResult<Py<PyAny>> {
+        let cm = self.cm.clone();
+        let encoding = params.codec.clone();
+        let result = tokio_rt()
+            .spawn(async move {
+                let cm = cm.read().await;
+                match cm.query(cmd.clone(), params.clone()).await {
+                    Err(e)
+                        if e.redis_error().map(|e| e.kind())
+                            == Some(ErrorKind::Server(ServerErrorKind::NoScript)) =>
+                    {
+                        // the script must be loaded before EVALSHA is sent again
+                        let load = redis::cmd("SCRIPT").arg("LOAD").arg(source).to_owned();
+                        let load_params = Params {
+                            keys: params.keys.clone(),
+                            ..Params::from(&load)
+                        };
+                        cm.query(load, load_params).await?.extract_error()?;
+                        cm.query(cmd, params).await
+                    }
+                    r => r,
+                }
+            })
+            .await
+            .unwrap()?;
+        Python::attach(|py| types::to_object(py, result, encoding))
+    }
+
+    pub async fn execute_batch(&self, batch: Vec<(Cmd, Params)>) -> PyResult<Py<PyAny>> {
+        let cm = self.cm.clone();
+        let encodings: Vec<types::Codec> = batch.iter().map(|(_, p)| p.codec.clone()).collect();
+        let result = tokio_rt()
+            .spawn(async move { cm.read().await.execute_batch(batch).await })
+            .await
+            .unwrap()?;
+        Python::attach(|py| {
+            let mut values = vec![];
+            for (value, encoding) in result.into_iter().zip(encodings) {
+                values.push(types::to_object(py, value, encoding)?);
+            }
+            pyo3::types::PyList::new(py, values)?.into_py_any(py)
+        })
+    }
+
+    pub async fn masters(&self) -> PyResult<Vec<String>> {
+        let cm = self.cm.clone();
+        let masters = tokio_rt()
+            .spawn(async move { cm.read().await.pool.masters().await })
+            .await
+            .unwrap()?;
+        Ok(masters)
+    }
+
+    pub async fn execute_on(
+        &self,
+        addr: Option<String>,
+        cmd: Cmd,
+        params: Params,
+    ) -> PyResult<Value> {
+        let cm = self.cm.clone();
+        let value = tokio_rt()
+            .spawn(async move { cm.read().await.query_on(addr, cmd, params).await })
+            .await
+            .unwrap()?;
+        Ok(value)
+    }
+
+    pub async fn get_connection(
+        &self,
+        params: Params,
+    ) -> PyResult<Box<dyn Connection + Send + Sync>> {
+        let cm = self.cm.clone();
+        let conn = tokio_rt()
+            .spawn(async move { cm.read().await.pool.get_connection(params).await })
+            .await
+            .unwrap()?;
+        Ok(conn)
+    }
+
+    pub async fn subscribe(
+        &self,
+        channels: Vec<Vec<u8>>,
+        patterns: Vec<Vec<u8>>,
+    ) -> PyResult<pubsub::Receiver> {
+        let cm = self.cm.clone();
+        let rx = tokio_rt()
+            .spawn(async move { cm.read().await.pool.subscribe(channels, patterns).await })
+            .await
+            .unwrap()?;
+        Ok(rx)
+    }
+
     pub async fn fetch_dict(&self, cmd: Cmd, params: Params) -> PyResult<Py<PyAny>> {
         let cm = self.cm.clone();
         let encoding = params.codec.clone();
         let block = params.block;
         let pool_result = tokio_rt()
-            .spawn(async move { cm.read().await.pool.execute(cmd, params).await })
+            .spawn(async move { cm.read().await.query(cmd, params).await })
             .await
             .unwrap();
 
@@ -67,6 +184,22 @@ impl AsyncClientResult {
         }
     }
 
+    pub async fn fetch_entries(&self, cmd: Cmd, params: Params) -> PyResult<Py<PyAny>> {
+        let cm = self.cm.clone();
+        let encoding = params.codec.clone();
+        let block = params.block;
+        let pool_result = tokio_rt()
+            .spawn(async move { cm.read().await.query(cmd, params).await })
+            .await
+            .unwrap();
+
+        match pool_result {
+            Ok(value) => Python::attach(|py| stream::to_entries(py, value, encoding)),
+            Err(_) if block => Python::attach(|py| pyo3::types::PyList::empty(py).into_py_any(py)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     pub async fn fetch<T>(&self, cmd: Cmd, params: Params) -> PyResult<T>
     where
         T: FromRedisValue + Send + 'static,
@@ -1,34 +1,54 @@
 use std::collections::HashMap;
 
-use crate::{cluster_bb8::ClusterManager, command::Params, error, pool::Pool};
+use crate::{
+    cluster_bb8::ClusterManager,
+    command::Params,
+    config::Config,
+    error,
+    pool::{Connection, Pool},
+    pubsub,
+    retry::Retry,
+    shards::{self, Slots},
+};
 use async_trait::async_trait;
-use redis::{aio::ConnectionLike, cluster::ClusterClient, Cmd, IntoConnectionInfo};
+use redis::{
+    aio::ConnectionLike,
+    cluster_routing::{RoutingInfo, SingleNodeRoutingInfo},
+    Cmd, ConnectionInfo,
+};
 
 pub struct Cluster {
     pool: bb8::Pool<ClusterManager>,
     connection: redis::cluster_async::ClusterConnection,
+    info: ConnectionInfo,
+    retry: Retry,
 }
 
 impl Cluster {
-    pub async fn new<T>(initial_nodes: Vec<T>, max_size: u32) -> Result<Self, error::RedisError>
-    where
-        T: IntoConnectionInfo + Clone,
-    {
-        let client = ClusterClient::new(initial_nodes.clone())?;
+    pub async fn new(config: Config) -> Result<Self, error::RedisError> {
+        let client = config.cluster_client()?;
+        let info = config.initial_nodes[0].clone();
         let connection = client.get_async_connection().await?;
 
-        let pool = bb8::Pool::builder()
-            .max_size(max_size)
-            .build(ClusterManager::new(initial_nodes)?)
-            .await?;
+        let mut builder = bb8::Pool::builder().max_size(config.max_size);
+        if let Some(connect_timeout) = config.connect_timeout {
+            builder = builder.connection_timeout(connect_timeout);
+        }
+        let pool = builder.build(ClusterManager::new(client)).await?;
 
-        Ok(Self { pool, connection })
+        Ok(Self {
+            pool,
+            connection,
+            info,
+            retry: Retry::new(config.retry),
+        })
     }
-}
 
-#[async_trait]
-impl Pool for Cluster {
-    async fn execute(&self, cmd: Cmd, params: Params) -> Result<redis::Value, error::RedisError> {
+    async fn execute_once(
+        &self,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
         let value = if params.block {
             let mut conn = self.pool.get().await?;
             conn.req_packed_command(&cmd).await?
@@ -38,8 +58,106 @@ impl Pool for Cluster {
         };
         Ok(value)
     }
+}
+
+#[async_trait]
+impl Pool for Cluster {
+    async fn execute(&self, cmd: Cmd, params: Params) -> Result<redis::Value, error::RedisError> {
+        self.retry
+            .execute(&cmd, || self.execute_once(cmd.clone(), params.clone()))
+            .await
+    }
+
+    async fn execute_batch(
+        &self,
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError> {
+        // Commands of a slot are pipelined in order, slots are sent concurrently
+        let count = batch.len();
+        let mut groups: HashMap<Option<u16>, (Vec<usize>, redis::Pipeline, bool)> = HashMap::new();
+        for (n, (cmd, params)) in batch.into_iter().enumerate() {
+            let key = params.keys.first().map(|key| shards::slot(key));
+            let (indexes, pipe, block) = groups
+                .entry(key)
+                .or_insert_with(|| (vec![], redis::pipe(), false));
+            indexes.push(n);
+            pipe.add_command(cmd);
+            *block |= params.block;
+        }
+        let mut tasks = tokio::task::JoinSet::new();
+        for (indexes, pipe, block) in groups.into_values() {
+            let connection = self.connection.clone();
+            let pool = self.pool.clone();
+            tasks.spawn(async move {
+                let values = if block {
+                    let mut conn = pool.get().await?;
+                    conn.req_packed_commands(&pipe, 0, indexes.len()).await?
+                } else {
+                    let mut conn = connection;
+                    conn.req_packed_commands(&pipe, 0, indexes.len()).await?
+                };
+                Ok::<_, error::RedisError>((indexes, values))
+            });
+        }
+        let mut result = vec![redis::Value::Nil; count];
+        while let Some(r) = tasks.join_next().await {
+            let (indexes, values) = r??;
+            for (n, value) in indexes.into_iter().zip(values) {
+                result[n] = value;
+            }
+        }
+        Ok(result)
+    }
+
+    async fn get_connection(
+        &self,
+        _params: Params,
+    ) -> Result<Box<dyn Connection + Send + Sync>, error::RedisError> {
+        Err(error::RedisError::CommandError(
+            "Transactions are not supported in cluster mode, use shards".into(),
+        ))
+    }
+
+    async fn masters(&self) -> Result<Vec<String>, error::RedisError> {
+        let mut conn = self.connection.clone();
+        let slots: Slots = redis::cmd("CLUSTER")
+            .arg("SLOTS")
+            .query_async(&mut conn)
+            .await?;
+        Ok(slots.get_masters())
+    }
+
+    async fn node_addr(&self, _cmd: &Cmd, _params: &Params) -> Option<String> {
+        // routing is done inside the cluster connection
+        None
+    }
+
+    async fn execute_on(
+        &self,
+        addr: &str,
+        cmd: Cmd,
+        _params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        let (host, port) = addr
+            .rsplit_once(':')
+            .ok_or(error::RedisError::NotFoundNode)?;
+        let routing = RoutingInfo::SingleNode(SingleNodeRoutingInfo::ByAddress {
+            host: host.to_string(),
+            port: port.parse().map_err(|_| error::RedisError::NotFoundNode)?,
+        });
+        let mut conn = self.connection.clone();
+        Ok(conn.route_command(cmd, routing).await?)
+    }
+
+    async fn subscribe(
+        &self,
+        channels: Vec<Vec<u8>>,
+        patterns: Vec<Vec<u8>>,
+    ) -> Result<pubsub::Receiver, error::RedisError> {
+        pubsub::listen(self.info.clone(), channels, patterns, None).await
+    }
 
-    fn status(&self) -> HashMap<&str, redis::Value> {
+    fn status(&self) -> HashMap<&'static str, redis::Value> {
         let mut result = HashMap::new();
         result.insert("closed", redis::Value::Boolean(false));
         result.insert("impl", redis::Value::SimpleString("cluster_async".into()));
@@ -54,6 +172,7 @@ impl Pool for Cluster {
             "idle_connections",
             redis::Value::Int(state.idle_connections as i64),
         );
+        self.retry.status(&mut result);
         result
     }
 }
@@ -1,6 +1,6 @@
 use redis::{
-    cluster::ClusterClient, cluster_async::ClusterConnection, ErrorKind, IntoConnectionInfo,
-    RedisError, ServerErrorKind,
+    cluster::ClusterClient, cluster_async::ClusterConnection, ErrorKind, RedisError,
+    ServerErrorKind,
 };
 
 pub struct ClusterManager {
@@ -8,12 +8,8 @@ pub struct ClusterManager {
 }
 
 impl ClusterManager {
-    pub fn new<T>(initial_nodes: Vec<T>) -> Result<Self, RedisError>
-    where
-        T: IntoConnectionInfo,
-    {
-        let client = ClusterClient::new(initial_nodes)?;
-        Ok(Self { client })
+    pub fn new(client: ClusterClient) -> Self {
+        Self { client }
     }
 }
 
@@ -0,0 +1,293 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use pyo3::{
+    exceptions::{PyKeyError, PyValueError},
+    prelude::*,
+    types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple},
+    IntoPyObjectExt,
+};
+
+/// Codec usable as `encoding=` once registered by name
+pub trait CustomCodec: Send + Sync {
+    fn decode(&self, py: Python, data: &[u8]) -> PyResult<Py<PyAny>>;
+    fn encode(&self, value: &Bound<PyAny>) -> PyResult<Vec<u8>>;
+}
+
+/// Names `encoding=` resolves to a builtin codec, not available to `register_codec`
+pub const BUILTIN: [&str; 12] = [
+    "bytes", "utf-8", "utf8", "UTF8", "UTF-8", "str", "float", "int", "info", "json", "pickle",
+    "msgpack",
+];
+
+fn get_codecs() -> &'static RwLock<HashMap<String, Arc<dyn CustomCodec>>> {
+    static CODECS: OnceLock<RwLock<HashMap<String, Arc<dyn CustomCodec>>>> = OnceLock::new();
+    CODECS.get_or_init(|| {
+        let mut codecs: HashMap<String, Arc<dyn CustomCodec>> = HashMap::new();
+        codecs.insert("pickle".into(), Arc::new(Pickle));
+        codecs.insert("msgpack".into(), Arc::new(MsgPack));
+        RwLock::new(codecs)
+    })
+}
+
+pub fn register(name: String, codec: Arc<dyn CustomCodec>) {
+    get_codecs().write().unwrap().insert(name, codec);
+}
+
+pub fn is_registered(name: &str) -> bool {
+    get_codecs().read().unwrap().contains_key(name)
+}
+
+pub fn get(name: &str) -> PyResult<Arc<dyn CustomCodec>> {
+    get_codecs()
+        .read()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| PyKeyError::new_err(format!("Unknown codec: {name}")))
+}
+
+/// Python callables `decode(bytes)` and `encode(obj) -> bytes`
+pub struct PyCodec {
+    decode: Py<PyAny>,
+    encode: Option<Py<PyAny>>,
+}
+
+impl PyCodec {
+    pub fn new(decode: Py<PyAny>, encode: Option<Py<PyAny>>) -> Self {
+        Self { decode, encode }
+    }
+}
+
+impl CustomCodec for PyCodec {
+    fn decode(&self, py: Python, data: &[u8]) -> PyResult<Py<PyAny>> {
+        self.decode.call1(py, (PyBytes::new(py, data),))
+    }
+
+    fn encode(&self, value: &Bound<PyAny>) -> PyResult<Vec<u8>> {
+        match &self.encode {
+            Some(encode) => encode.bind(value.py()).call1((value,))?.extract(),
+            None => Err(PyValueError::new_err("Codec has no encoder")),
+        }
+    }
+}
+
+/// Python objects serialized with the standard pickle module
+struct Pickle;
+
+impl CustomCodec for Pickle {
+    fn decode(&self, py: Python, data: &[u8]) -> PyResult<Py<PyAny>> {
+        let pickle = py.import("pickle")?;
+        Ok(pickle
+            .call_method1("loads", (PyBytes::new(py, data),))?
+            .unbind())
+    }
+
+    fn encode(&self, value: &Bound<PyAny>) -> PyResult<Vec<u8>> {
+        let pickle = value.py().import("pickle")?;
+        pickle.call_method1("dumps", (value,))?.extract()
+    }
+}
+
+/// MessagePack for None, bool, int, float, str, bytes, list, tuple and dict
+struct MsgPack;
+
+/// Deepest list/dict nesting packed or unpacked, keeps hostile payloads off the stack limit
+const MAX_DEPTH: usize = 512;
+
+impl CustomCodec for MsgPack {
+    fn decode(&self, py: Python, data: &[u8]) -> PyResult<Py<PyAny>> {
+        let mut data = data;
+        let value = unpack(py, &mut data, 0)?;
+        if !data.is_empty() {
+            return Err(PyValueError::new_err("Extra data after msgpack value"));
+        }
+        Ok(value)
+    }
+
+    fn encode(&self, value: &Bound<PyAny>) -> PyResult<Vec<u8>> {
+        let mut out = vec![];
+        pack(value, &mut out, 0)?;
+        Ok(out)
+    }
+}
+
+fn pack_len(out: &mut Vec<u8>, len: usize, markers: [u8; 3]) -> PyResult<()> {
+    match len {
+        0..=0xff if markers[0] != 0 => out.extend([markers[0], len as u8]),
+        0..=0xffff => {
+            out.push(markers[1]);
+            out.extend((len as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(markers[2]);
+            out.extend((len as u32).to_be_bytes());
+        }
+        _ => return Err(PyValueError::new_err("Value too large for msgpack")),
+    }
+    Ok(())
+}
+
+fn nested(depth: usize) -> PyResult<usize> {
+    if depth >= MAX_DEPTH {
+        return Err(PyValueError::new_err(format!(
+            "msgpack nesting deeper than {MAX_DEPTH}"
+        )));
+    }
+    Ok(depth + 1)
+}
+
+fn pack(value: &Bound<PyAny>, out: &mut Vec<u8>, depth: usize) -> PyResult<()> {
+    if value.is_none() {
+        out.push(0xc0);
+    } else if let Ok(b) = value.cast::<PyBool>() {
+        out.push(if b.is_true() { 0xc3 } else { 0xc2 });
+    } else if let Ok(i) = value.cast::<PyInt>() {
+        match i.extract::<i64>() {
+            Ok(i @ -32..=127) => out.push(i as u8),
+            Ok(i) if i >= 0 => {
+                out.push(0xcf);
+                out.extend((i as u64).to_be_bytes());
+            }
+            Ok(i) => {
+                out.push(0xd3);
+                out.extend(i.to_be_bytes());
+            }
+            Err(_) => {
+                out.push(0xcf);
+                out.extend(i.extract::<u64>()?.to_be_bytes());
+            }
+        }
+    } else if let Ok(f) = value.cast::<PyFloat>() {
+        out.push(0xcb);
+        out.extend(f.value().to_be_bytes());
+    } else if let Ok(s) = value.cast::<PyString>() {
+        let s = s.to_str()?.as_bytes();
+        match s.len() {
+            len @ 0..=31 => out.push(0xa0 | len as u8),
+            len => pack_len(out, len, [0xd9, 0xda, 0xdb])?,
+        }
+        out.extend(s);
+    } else if let Ok(b) = value.cast::<PyBytes>() {
+        let b = b.as_bytes();
+        pack_len(out, b.len(), [0xc4, 0xc5, 0xc6])?;
+        out.extend(b);
+    } else if let Ok(d) = value.cast::<PyDict>() {
+        let depth = nested(depth)?;
+        match d.len() {
+            len @ 0..=15 => out.push(0x80 | len as u8),
+            len => pack_len(out, len, [0, 0xde, 0xdf])?,
+        }
+        for (k, v) in d.iter() {
+            pack(&k, out, depth)?;
+            pack(&v, out, depth)?;
+        }
+    } else if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+        let depth = nested(depth)?;
+        match value.len()? {
+            len @ 0..=15 => out.push(0x90 | len as u8),
+            len => pack_len(out, len, [0, 0xdc, 0xdd])?,
+        }
+        for item in value.try_iter()? {
+            pack(&item?, out, depth)?;
+        }
+    } else {
+        return Err(PyValueError::new_err(format!(
+            "Unsupported msgpack type: {}",
+            value.get_type().name()?
+        )));
+    }
+    Ok(())
+}
+
+fn take<'a>(data: &mut &'a [u8], n: usize) -> PyResult<&'a [u8]> {
+    if data.len() < n {
+        return Err(PyValueError::new_err("Truncated msgpack data"));
+    }
+    let (head, tail) = data.split_at(n);
+    *data = tail;
+    Ok(head)
+}
+
+fn take_len(data: &mut &[u8], size: usize) -> PyResult<usize> {
+    let mut len = 0;
+    for b in take(data, size)? {
+        len = len << 8 | *b as usize;
+    }
+    Ok(len)
+}
+
+fn unpack_str(py: Python, data: &mut &[u8], len: usize) -> PyResult<Py<PyAny>> {
+    let s =
+        std::str::from_utf8(take(data, len)?).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    s.into_py_any(py)
+}
+
+fn unpack_array(py: Python, data: &mut &[u8], len: usize, depth: usize) -> PyResult<Py<PyAny>> {
+    let depth = nested(depth)?;
+    let mut items = Vec::with_capacity(len.min(data.len()));
+    for _ in 0..len {
+        items.push(unpack(py, data, depth)?);
+    }
+    PyList::new(py, items)?.into_py_any(py)
+}
+
+fn unpack_map(py: Python, data: &mut &[u8], len: usize, depth: usize) -> PyResult<Py<PyAny>> {
+    let depth = nested(depth)?;
+    let result = PyDict::new(py);
+    for _ in 0..len {
+        let key = unpack(py, data, depth)?;
+        result.set_item(key, unpack(py, data, depth)?)?;
+    }
+    result.into_py_any(py)
+}
+
+fn unpack(py: Python, data: &mut &[u8], depth: usize) -> PyResult<Py<PyAny>> {
+    let marker = take(data, 1)?[0];
+    let int = |data: &mut &[u8], n: usize| -> PyResult<[u8; 8]> {
+        let mut buf = [0; 8];
+        buf[8 - n..].copy_from_slice(take(data, n)?);
+        Ok(buf)
+    };
+    match marker {
+        0x00..=0x7f => (marker as i64).into_py_any(py),
+        0x80..=0x8f => unpack_map(py, data, (marker & 0x0f) as usize, depth),
+        0x90..=0x9f => unpack_array(py, data, (marker & 0x0f) as usize, depth),
+        0xa0..=0xbf => unpack_str(py, data, (marker & 0x1f) as usize),
+        0xc0 => Ok(py.None()),
+        0xc2 => false.into_py_any(py),
+        0xc3 => true.into_py_any(py),
+        0xc4..=0xc6 => {
+            let len = take_len(data, 1 << (marker - 0xc4))?;
+            PyBytes::new(py, take(data, len)?).into_py_any(py)
+        }
+        0xca => f32::from_be_bytes(take(data, 4)?.try_into().unwrap()).into_py_any(py),
+        0xcb => f64::from_be_bytes(take(data, 8)?.try_into().unwrap()).into_py_any(py),
+        0xcc..=0xcf => u64::from_be_bytes(int(data, 1 << (marker - 0xcc))?).into_py_any(py),
+        0xd0..=0xd3 => {
+            let n = 1 << (marker - 0xd0);
+            let shift = 64 - 8 * n as u32;
+            // sign-extend from the encoded width
+            let value = (i64::from_be_bytes(int(data, n)?) << shift) >> shift;
+            value.into_py_any(py)
+        }
+        0xd9..=0xdb => {
+            let len = take_len(data, 1 << (marker - 0xd9))?;
+            unpack_str(py, data, len)
+        }
+        0xdc | 0xdd => {
+            let len = take_len(data, 2 << (marker - 0xdc))?;
+            unpack_array(py, data, len, depth)
+        }
+        0xde | 0xdf => {
+            let len = take_len(data, 2 << (marker - 0xde))?;
+            unpack_map(py, data, len, depth)
+        }
+        0xe0..=0xff => (marker as i8 as i64).into_py_any(py),
+        _ => Err(PyValueError::new_err(format!(
+            "Unsupported msgpack marker: {marker:#x}"
+        ))),
+    }
+}
@@ -1,10 +1,16 @@
-use crate::types::{Codec, Str};
+use std::time::Duration;
+
+use crate::{
+    key_spec::{self, KeySpecs},
+    types::{Codec, Str},
+};
 
 #[derive(Default, Clone, PartialEq, Eq, Debug)]
 pub struct Params {
     pub keys: Vec<Vec<u8>>,
     pub block: bool,
     pub codec: Codec,
+    pub timeout: Option<Duration>,
 }
 
 impl From<Codec> for Params {
@@ -43,80 +49,224 @@ impl From<&Vec<Str>> for Params {
     }
 }
 
+fn args(cmd: &redis::Cmd) -> Vec<&[u8]> {
+    cmd.args_iter()
+        .filter_map(|arg| match arg {
+            redis::Arg::Simple(s) => Some(s),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Keys found by the bundled key specs
 impl From<&redis::Cmd> for Params {
     fn from(cmd: &redis::Cmd) -> Self {
-        let mut result = Self::default();
-        let mut cmd_iter = cmd.args_iter().filter_map(|arg| match arg {
+        let (keys, block) = key_spec::extract(&args(cmd));
+        Self {
+            keys,
+            block,
+            ..Default::default()
+        }
+    }
+}
+
+impl Params {
+    /// Keys found by the key specs loaded from the server
+    pub fn with_specs(cmd: &redis::Cmd, specs: &KeySpecs) -> Self {
+        let (keys, block) = specs.extract(&args(cmd));
+        Self {
+            keys,
+            block,
+            ..Default::default()
+        }
+    }
+}
+
+/// Uppercased command name
+pub fn command_name(cmd: &redis::Cmd) -> String {
+    match cmd.args_iter().next() {
+        Some(redis::Arg::Simple(name)) => String::from_utf8_lossy(name).to_ascii_uppercase(),
+        _ => String::new(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Merge {
+    Values,
+    Sum,
+    AllOk,
+}
+
+/// Multi-key commands that can be split by slot, with the step between keys in args
+pub fn multi_key(name: &[u8]) -> Option<(Merge, usize)> {
+    match name {
+        b"MGET" => Some((Merge::Values, 1)),
+        b"DEL" | b"EXISTS" | b"UNLINK" | b"TOUCH" => Some((Merge::Sum, 1)),
+        b"MSET" => Some((Merge::AllOk, 2)),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fanout {
+    AllMasters,
+    AllNodes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Concat,
+    AllOk,
+    ByNode,
+    And,
+}
+
+/// Node-wide commands sent to every node in cluster mode, with the rule merging the replies
+pub fn fanout(cmd: &redis::Cmd) -> Option<(Fanout, Aggregate)> {
+    let mut cmd_iter = cmd.args_iter().filter_map(|arg| match arg {
+        redis::Arg::Simple(s) => Some(s.to_ascii_uppercase()),
+        _ => None,
+    });
+    let name = cmd_iter.next()?;
+    let sub = cmd_iter.next().unwrap_or_default();
+    match (name.as_slice(), sub.as_slice()) {
+        (b"DBSIZE", _) => Some((Fanout::AllMasters, Aggregate::Sum)),
+        (b"KEYS", _) => Some((Fanout::AllMasters, Aggregate::Concat)),
+        (b"FLUSHDB" | b"FLUSHALL", _) => Some((Fanout::AllMasters, Aggregate::AllOk)),
+        (b"SCRIPT", b"LOAD" | b"FLUSH") => Some((Fanout::AllMasters, Aggregate::AllOk)),
+        (b"SCRIPT", b"EXISTS") => Some((Fanout::AllNodes, Aggregate::And)),
+        (b"FUNCTION", b"LOAD" | b"DELETE" | b"FLUSH" | b"RESTORE") => {
+            Some((Fanout::AllMasters, Aggregate::AllOk))
+        }
+        (b"CONFIG", b"SET" | b"RESETSTAT") => Some((Fanout::AllNodes, Aggregate::AllOk)),
+        (b"CONFIG", b"GET") | (b"INFO", _) => Some((Fanout::AllNodes, Aggregate::ByNode)),
+        _ => None,
+    }
+}
+
+const READONLY_COMMANDS: &[&[u8]] = &[
+    b"BITCOUNT",
+    b"BITPOS",
+    b"EXISTS",
+    b"FCALL_RO",
+    b"GET",
+    b"GETBIT",
+    b"GETRANGE",
+    b"HEXISTS",
+    b"HGET",
+    b"HGETALL",
+    b"HKEYS",
+    b"HLEN",
+    b"HMGET",
+    b"HSTRLEN",
+    b"HVALS",
+    b"LINDEX",
+    b"LLEN",
+    b"LPOS",
+    b"LRANGE",
+    b"MGET",
+    b"PTTL",
+    b"SCARD",
+    b"SISMEMBER",
+    b"SMEMBERS",
+    b"SMISMEMBER",
+    b"SRANDMEMBER",
+    b"STRLEN",
+    b"TTL",
+    b"TYPE",
+    b"XLEN",
+    b"XRANGE",
+    b"XREVRANGE",
+    b"ZCARD",
+    b"ZCOUNT",
+    b"ZLEXCOUNT",
+    b"ZMSCORE",
+    b"ZRANGE",
+    b"ZRANGEBYLEX",
+    b"ZRANGEBYSCORE",
+    b"ZRANK",
+    b"ZREVRANGE",
+    b"ZREVRANGEBYSCORE",
+    b"ZREVRANK",
+    b"ZSCORE",
+];
+
+pub fn is_readonly(cmd: &redis::Cmd) -> bool {
+    match cmd.args_iter().next() {
+        Some(redis::Arg::Simple(name)) => READONLY_COMMANDS
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(name)),
+        _ => false,
+    }
+}
+
+/// Server-side timeout of a blocking command, None when it may block forever
+pub fn block_timeout(cmd: &redis::Cmd) -> Option<Duration> {
+    let args: Vec<&[u8]> = cmd
+        .args_iter()
+        .filter_map(|arg| match arg {
             redis::Arg::Simple(s) => Some(s),
             _ => None,
-        });
-        let key = match cmd_iter.next() {
-            Some(b"CLUSTER") => match cmd_iter.next() {
-                Some(b"KEYSLOT") => cmd_iter.next(),
-                _ => None,
-            },
-            Some(b"XGROUP" | b"XINFO") => {
-                cmd_iter.next();
-                cmd_iter.next()
-            }
-            Some(b"ZMPOP") => {
-                cmd_iter.next();
-                cmd_iter.next()
-            }
-            Some(b"ZDIFF" | b"ZINTER" | b"ZINTERCARD" | b"ZINTERSTORE" | b"ZUNION") => {
-                cmd_iter.next();
-                cmd_iter.next()
-            }
-            Some(b"BZMPOP") => {
-                result.block = true;
-                cmd_iter.next();
-                cmd_iter.next();
-                cmd_iter.next()
-            }
-            Some(b"BZPOPMAX" | b"BZPOPMIN") => {
-                result.block = true;
-                cmd_iter.next()
-            }
-            Some(b"EVAL" | b"ZDIFFSTORE" | b"ZUNIONSTORE") => {
-                cmd_iter.next();
-                cmd_iter.next();
-                cmd_iter.next()
-            }
-            Some(b"XREAD" | b"XREADGROUP") => {
-                loop {
-                    match cmd_iter.next() {
-                        Some(b"BLOCK") => result.block = true,
-                        Some(b"STREAMS") | None => break,
-                        _ => continue,
-                    }
-                }
-                cmd_iter.next()
-            }
-            Some(b"MIGRATE") => {
-                loop {
-                    if let Some(b"KEYS") | None = cmd_iter.next() {
-                        break;
-                    }
-                }
-                cmd_iter.next()
-            }
-            Some(b"INFO" | b"CLIENT" | b"KEYS") => None,
-            Some(b"BLPOP" | b"BRPOP") => {
-                result.block = true;
-                cmd_iter.next()
+        })
+        .collect();
+    let parse =
+        |arg: Option<&&[u8]>| -> Option<f64> { std::str::from_utf8(arg?).ok()?.parse().ok() };
+    let name = args.first()?.to_ascii_uppercase();
+    let seconds = match name.as_slice() {
+        b"BLPOP" | b"BRPOP" | b"BRPOPLPUSH" | b"BLMOVE" | b"BZPOPMIN" | b"BZPOPMAX" => {
+            parse(args.last())
+        }
+        b"BLMPOP" | b"BZMPOP" => parse(args.get(1)),
+        b"XREAD" | b"XREADGROUP" => {
+            let block = args.iter().position(|a| a.eq_ignore_ascii_case(b"BLOCK"));
+            match block {
+                Some(n) => parse(args.get(n + 1)).map(|ms| ms / 1000.0),
+                None => return Some(Duration::ZERO),
             }
-            _ => cmd_iter.next(),
-        };
-        if let Some(key) = key {
-            result.keys.push(key.to_vec());
         }
-        result
+        b"WAIT" => parse(args.get(2)).map(|ms| ms / 1000.0),
+        b"WAITAOF" => parse(args.get(3)).map(|ms| ms / 1000.0),
+        _ => return Some(Duration::ZERO),
+    };
+    match seconds {
+        Some(s) if s > 0.0 => Some(Duration::from_secs_f64(s)),
+        Some(_) => None,
+        None => Some(Duration::ZERO),
+    }
+}
+
+// Writes leaving the same data when applied twice, the reply of a replay may differ
+// (e.g. DEL returns 0 once the key was deleted by the first attempt).
+// Relative EXPIRE/PEXPIRE are left out, a replay moves the deadline to the retry time
+const IDEMPOTENT_COMMANDS: &[&[u8]] = &[
+    b"DEL", b"HDEL", b"HSET", b"PERSIST", b"SADD", b"SREM", b"UNLINK", b"ZREM",
+];
+
+// SET options making the outcome depend on the previous value
+const SET_CONDITIONS: &[&[u8]] = &[b"NX", b"XX", b"GET", b"IFEQ"];
+
+pub fn is_idempotent(cmd: &redis::Cmd) -> bool {
+    let mut args = cmd.args_iter().filter_map(|arg| match arg {
+        redis::Arg::Simple(arg) => Some(arg),
+        _ => None,
+    });
+    match args.next() {
+        Some(name) if name.eq_ignore_ascii_case(b"SET") => !args
+            .skip(2)
+            .any(|arg| SET_CONDITIONS.iter().any(|c| c.eq_ignore_ascii_case(arg))),
+        Some(name) => IDEMPOTENT_COMMANDS
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(name)),
+        None => false,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Params;
+    use std::time::Duration;
+
+    use super::{block_timeout, fanout, is_idempotent, is_readonly, Aggregate, Fanout, Params};
 
     #[test]
     fn cmd_to_param_get() {
@@ -138,4 +288,108 @@ mod tests {
 
         assert_eq!(param, result);
     }
+
+    #[test]
+    fn cmd_to_param_mget() {
+        let cmd = redis::cmd("MGET").arg("a").arg("b").arg("c").to_owned();
+        let param = Params::from(&cmd);
+
+        assert_eq!(
+            param.keys,
+            vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]
+        );
+    }
+
+    #[test]
+    fn cmd_to_param_mset() {
+        let cmd = redis::cmd("MSET")
+            .arg("a")
+            .arg(1)
+            .arg("b")
+            .arg(2)
+            .to_owned();
+        let param = Params::from(&cmd);
+
+        assert_eq!(param.keys, vec![b"a".to_vec(), b"b".to_vec()]);
+    }
+
+    #[test]
+    fn cmd_to_param_fcall() {
+        let cmd = redis::cmd("FCALL")
+            .arg("f")
+            .arg(2)
+            .arg("a")
+            .arg("b")
+            .arg("arg")
+            .to_owned();
+        let param = Params::from(&cmd);
+
+        assert_eq!(param.keys, vec![b"a".to_vec(), b"b".to_vec()]);
+    }
+
+    #[test]
+    fn cmd_fanout() {
+        let cmd = redis::cmd("DBSIZE").to_owned();
+        assert_eq!(fanout(&cmd), Some((Fanout::AllMasters, Aggregate::Sum)));
+        let cmd = redis::cmd("script").arg("load").arg("return 1").to_owned();
+        assert_eq!(fanout(&cmd), Some((Fanout::AllMasters, Aggregate::AllOk)));
+        let cmd = redis::cmd("FUNCTION").arg("LOAD").arg("code").to_owned();
+        assert_eq!(fanout(&cmd), Some((Fanout::AllMasters, Aggregate::AllOk)));
+        let cmd = redis::cmd("SCRIPT").arg("EXISTS").arg("sha").to_owned();
+        assert_eq!(fanout(&cmd), Some((Fanout::AllNodes, Aggregate::And)));
+        let cmd = redis::cmd("CONFIG").arg("SET").arg("a").arg(1).to_owned();
+        assert_eq!(fanout(&cmd), Some((Fanout::AllNodes, Aggregate::AllOk)));
+        let cmd = redis::cmd("info").arg("server").to_owned();
+        assert_eq!(fanout(&cmd), Some((Fanout::AllNodes, Aggregate::ByNode)));
+        assert_eq!(fanout(&redis::cmd("GET").arg("a").to_owned()), None);
+    }
+
+    #[test]
+    fn cmd_readonly() {
+        assert!(is_readonly(&redis::cmd("GET").arg("a").to_owned()));
+        assert!(is_readonly(&redis::cmd("hgetall").arg("a").to_owned()));
+        assert!(!is_readonly(&redis::cmd("SET").arg("a").arg(1).to_owned()));
+    }
+
+    #[test]
+    fn cmd_block_timeout() {
+        let cmd = redis::cmd("BLPOP").arg("a").arg("b").arg(1.5).to_owned();
+        assert_eq!(block_timeout(&cmd), Some(Duration::from_millis(1500)));
+        let cmd = redis::cmd("BLPOP").arg("a").arg(0).to_owned();
+        assert_eq!(block_timeout(&cmd), None);
+        let cmd = redis::cmd("XREAD")
+            .arg("BLOCK")
+            .arg(200)
+            .arg("STREAMS")
+            .arg("s")
+            .arg("$")
+            .to_owned();
+        assert_eq!(block_timeout(&cmd), Some(Duration::from_millis(200)));
+        let cmd = redis::cmd("EVAL").arg("return 1").arg(0).to_owned();
+        assert_eq!(block_timeout(&cmd), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn cmd_idempotent() {
+        assert!(is_idempotent(&redis::cmd("del").arg("a").to_owned()));
+        assert!(!is_idempotent(&redis::cmd("INCR").arg("a").to_owned()));
+        assert!(!is_idempotent(
+            &redis::cmd("EXPIRE").arg("a").arg(10).to_owned()
+        ));
+        assert!(is_idempotent(
+            &redis::cmd("SET")
+                .arg("a")
+                .arg(1)
+                .arg("EX")
+                .arg(1)
+                .to_owned()
+        ));
+        assert!(!is_idempotent(
+            &redis::cmd("SET").arg("a").arg(1).arg("nx").to_owned()
+        ));
+        // option names are only looked up after the key and value
+        assert!(is_idempotent(
+            &redis::cmd("SET").arg("nx").arg("GET").to_owned()
+        ));
+    }
 }
@@ -1,4 +1,192 @@
-use redis::{ConnectionInfo, IntoConnectionInfo};
+use std::{collections::HashMap, time::Duration};
+
+use redis::{
+    cluster::ClusterClient, ConnectionAddr, ConnectionInfo, IntoConnectionInfo, ProtocolVersion,
+    RedisResult,
+};
+
+use crate::{
+    command::{is_idempotent, is_readonly},
+    shards::ShardNode,
+};
+
+#[derive(Clone, Default)]
+pub struct Tls {
+    pub ca_certs: Option<Vec<u8>>,
+    pub client_cert: Option<Vec<u8>>,
+    pub client_key: Option<Vec<u8>>,
+    pub insecure: bool,
+}
+
+impl Tls {
+    pub fn info_on(&self, info: ConnectionInfo) -> RedisResult<ConnectionInfo> {
+        let addr = match info.addr().clone() {
+            ConnectionAddr::Tcp(host, port) | ConnectionAddr::TcpTls { host, port, .. } => {
+                ConnectionAddr::TcpTls {
+                    host,
+                    port,
+                    insecure: self.insecure,
+                    tls_params: None,
+                }
+            }
+            addr => addr,
+        };
+        self.with_certificates(info.set_addr(addr))
+    }
+
+    #[cfg(feature = "rustls")]
+    pub fn certificates(&self) -> Option<redis::TlsCertificates> {
+        let client_tls = match (&self.client_cert, &self.client_key) {
+            (Some(client_cert), Some(client_key)) => Some(redis::ClientTlsConfig {
+                client_cert: client_cert.clone(),
+                client_key: client_key.clone(),
+            }),
+            _ => None,
+        };
+        if client_tls.is_none() && self.ca_certs.is_none() {
+            return None;
+        }
+        Some(redis::TlsCertificates {
+            client_tls,
+            root_cert: self.ca_certs.clone(),
+        })
+    }
+
+    #[cfg(feature = "rustls")]
+    fn with_certificates(&self, info: ConnectionInfo) -> RedisResult<ConnectionInfo> {
+        match self.certificates() {
+            Some(certs) => {
+                let client = redis::Client::build_with_tls(info, certs)?;
+                Ok(client.get_connection_info().clone())
+            }
+            None => Ok(info),
+        }
+    }
+
+    #[cfg(not(feature = "rustls"))]
+    fn with_certificates(&self, info: ConnectionInfo) -> RedisResult<ConnectionInfo> {
+        if self.ca_certs.is_some() || self.client_cert.is_some() || self.client_key.is_some() {
+            return Err(redis::RedisError::from((
+                redis::ErrorKind::InvalidClientConfig,
+                "TLS certificates require the rustls feature",
+            )));
+        }
+        Ok(info)
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum ReadFrom {
+    #[default]
+    Primary,
+    PreferReplica,
+    RoundRobin,
+}
+
+impl TryFrom<&str> for ReadFrom {
+    type Error = redis::RedisError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_ascii_lowercase().as_str() {
+            "primary" | "master" => Ok(Self::Primary),
+            "prefer_replica" | "replica" => Ok(Self::PreferReplica),
+            "round_robin" => Ok(Self::RoundRobin),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::InvalidClientConfig,
+                "Unsupported read_from",
+                value.to_string(),
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryOn {
+    Io,
+    TryAgain,
+    ClusterDown,
+    Loading,
+}
+
+impl TryFrom<&str> for RetryOn {
+    type Error = redis::RedisError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_ascii_lowercase().as_str() {
+            "io" => Ok(Self::Io),
+            "tryagain" => Ok(Self::TryAgain),
+            "clusterdown" => Ok(Self::ClusterDown),
+            "loading" => Ok(Self::Loading),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::InvalidClientConfig,
+                "Unsupported retry_on",
+                value.to_string(),
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    pub retry_on: Vec<RetryOn>,
+    pub retry_writes: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Read-only and idempotent commands get up to 3 attempts
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            retry_on: vec![
+                RetryOn::Io,
+                RetryOn::TryAgain,
+                RetryOn::ClusterDown,
+                RetryOn::Loading,
+            ],
+            retry_writes: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn disabled() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// The command may be sent again after a failure that could have applied it
+    pub fn can_replay(&self, cmd: &redis::Cmd) -> bool {
+        self.retry_writes || is_readonly(cmd) || is_idempotent(cmd)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HealthCheck {
+    pub interval: Duration,
+    pub failures: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientCache {
+    pub max_entries: usize,
+    pub ttl: Option<Duration>,
+}
+
+impl Default for ClientCache {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            ttl: None,
+        }
+    }
+}
 
 #[derive(Clone, Default)]
 pub struct Config {
@@ -9,6 +197,17 @@ pub struct Config {
     pub max_delay: Option<u64>, // ms
     pub shards: bool,
     pub mock: bool,
+    pub protocol: ProtocolVersion,
+    pub tls: Option<Tls>,
+    pub sentinels: Vec<ConnectionInfo>,
+    pub service_name: Option<String>,
+    pub read_from: ReadFrom,
+    pub max_redirects: Option<usize>,
+    pub client_cache: Option<ClientCache>,
+    pub retry: RetryPolicy,
+    pub connect_timeout: Option<Duration>,
+    pub response_timeout: Option<Duration>,
+    pub health_check: Option<HealthCheck>,
 }
 
 impl Config {
@@ -32,6 +231,102 @@ impl Config {
         Ok(())
     }
 
+    pub fn set_sentinels<T>(&mut self, sentinels: Vec<T>, service_name: String) -> RedisResult<()>
+    where
+        T: IntoConnectionInfo,
+    {
+        for sentinel in sentinels.into_iter() {
+            let info = self.apply_settings(sentinel.into_connection_info()?)?;
+            self.sentinels.push(info);
+        }
+        self.service_name = Some(service_name);
+        Ok(())
+    }
+
+    pub fn set_protocol(&mut self, protocol: u8) -> Result<(), redis::RedisError> {
+        self.protocol = match protocol {
+            2 => ProtocolVersion::RESP2,
+            3 => ProtocolVersion::RESP3,
+            _ => {
+                return Err(redis::RedisError::from((
+                    redis::ErrorKind::InvalidClientConfig,
+                    "Unsupported protocol",
+                    protocol.to_string(),
+                )))
+            }
+        };
+        let protocol = self.protocol;
+        for info in self
+            .initial_nodes
+            .iter_mut()
+            .chain(self.sentinels.iter_mut())
+        {
+            *info = with_protocol(info.clone(), protocol);
+        }
+        Ok(())
+    }
+
+    /// Protocol and TLS settings given so far applied to the connection info
+    fn apply_settings(&self, info: ConnectionInfo) -> RedisResult<ConnectionInfo> {
+        let info = with_protocol(info, self.protocol);
+        match self.tls {
+            Some(ref tls) => tls.info_on(info),
+            None => Ok(info),
+        }
+    }
+
+    /// Connection info of a discovered node, with the settings of the initial nodes
+    pub fn node_info(&self, addr: &str) -> RedisResult<ConnectionInfo> {
+        match self.initial_nodes.first() {
+            Some(info) => Ok(ShardNode::from(addr).info_on(info.clone())),
+            None => self.apply_settings(format!("redis://{addr}").into_connection_info()?),
+        }
+    }
+
+    pub fn set_client_cache(&mut self, options: HashMap<String, f64>) -> RedisResult<()> {
+        let mut cache = ClientCache::default();
+        for (k, v) in options.into_iter() {
+            match k.as_str() {
+                "max_entries" if v >= 0.0 => cache.max_entries = v as usize,
+                "ttl" if v > 0.0 => cache.ttl = Some(Duration::from_secs_f64(v)),
+                _ => {
+                    return Err(redis::RedisError::from((
+                        redis::ErrorKind::InvalidClientConfig,
+                        "Unsupported client_cache option",
+                        format!("{k}={v}"),
+                    )))
+                }
+            }
+        }
+        self.client_cache = Some(cache);
+        Ok(())
+    }
+
+    pub fn set_tls(&mut self, tls: Tls) -> Result<(), redis::RedisError> {
+        for info in self
+            .initial_nodes
+            .iter_mut()
+            .chain(self.sentinels.iter_mut())
+        {
+            *info = tls.info_on(info.clone())?;
+        }
+        self.tls = Some(tls);
+        Ok(())
+    }
+
+    pub fn cluster_client(&self) -> RedisResult<ClusterClient> {
+        let mut builder = ClusterClient::builder(self.initial_nodes.clone());
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connection_timeout(connect_timeout);
+        }
+        #[cfg(feature = "rustls")]
+        let builder = match self.tls.as_ref().and_then(Tls::certificates) {
+            Some(certs) => builder.certs(certs),
+            None => builder,
+        };
+        builder.build()
+    }
+
     pub fn set_features(&mut self, features: &[String]) {
         for feature in features.iter() {
             match feature.to_ascii_lowercase().as_str() {
@@ -42,3 +337,105 @@ impl Config {
         }
     }
 }
+
+fn with_protocol(info: ConnectionInfo, protocol: ProtocolVersion) -> ConnectionInfo {
+    let redis_settings = info.redis_settings().clone().set_protocol(protocol);
+    info.set_redis_settings(redis_settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::{ConnectionAddr, ProtocolVersion};
+
+    use std::{collections::HashMap, time::Duration};
+
+    use super::{ClientCache, Config, ReadFrom, RetryOn, RetryPolicy, Tls};
+
+    #[test]
+    fn set_tls() {
+        let mut config = Config::new();
+        config.set_nodes(vec!["redis://localhost:6379"]).unwrap();
+        let tls = Tls {
+            insecure: true,
+            ..Default::default()
+        };
+        config.set_tls(tls).unwrap();
+        let addr = config.initial_nodes[0].addr().clone();
+        assert!(matches!(
+            addr,
+            ConnectionAddr::TcpTls {
+                port: 6379,
+                insecure: true,
+                ..
+            }
+        ));
+    }
+
+    #[cfg(not(feature = "rustls"))]
+    #[test]
+    fn set_tls_certificates() {
+        let mut config = Config::new();
+        config.set_nodes(vec!["redis://localhost:6379"]).unwrap();
+        let tls = Tls {
+            ca_certs: Some(vec![]),
+            ..Default::default()
+        };
+        assert!(config.set_tls(tls).is_err());
+    }
+
+    #[test]
+    fn read_from() {
+        assert_eq!(ReadFrom::try_from("primary").unwrap(), ReadFrom::Primary);
+        assert_eq!(
+            ReadFrom::try_from("prefer_replica").unwrap(),
+            ReadFrom::PreferReplica
+        );
+        assert_eq!(
+            ReadFrom::try_from("ROUND_ROBIN").unwrap(),
+            ReadFrom::RoundRobin
+        );
+        assert!(ReadFrom::try_from("nearest").is_err());
+    }
+
+    #[test]
+    fn set_client_cache() {
+        let mut config = Config::new();
+        let options = HashMap::from([("max_entries".to_string(), 100.0), ("ttl".to_string(), 1.5)]);
+        config.set_client_cache(options).unwrap();
+        assert_eq!(
+            config.client_cache,
+            Some(ClientCache {
+                max_entries: 100,
+                ttl: Some(Duration::from_millis(1500)),
+            })
+        );
+        let options = HashMap::from([("size".to_string(), 100.0)]);
+        assert!(config.set_client_cache(options).is_err());
+    }
+
+    #[test]
+    fn retry_on() {
+        assert_eq!(RetryOn::try_from("IO").unwrap(), RetryOn::Io);
+        assert_eq!(RetryOn::try_from("tryagain").unwrap(), RetryOn::TryAgain);
+        assert!(RetryOn::try_from("moved").is_err());
+        assert_eq!(Config::default().retry.max_attempts, 3);
+        assert_eq!(RetryPolicy::disabled().max_attempts, 1);
+    }
+
+    #[test]
+    fn set_protocol_sentinels() {
+        let mut config = Config::new();
+        config
+            .set_sentinels(vec!["redis://localhost:26379"], "mymaster".into())
+            .unwrap();
+        config.set_protocol(3).unwrap();
+        config
+            .set_sentinels(vec!["redis://localhost:26380"], "mymaster".into())
+            .unwrap();
+        for info in config.sentinels.iter() {
+            assert_eq!(info.redis_settings().protocol(), ProtocolVersion::RESP3);
+        }
+        let info = config.node_info("localhost:6380").unwrap();
+        assert_eq!(info.redis_settings().protocol(), ProtocolVersion::RESP3);
+    }
+}
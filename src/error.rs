@@ -1,3 +1,11 @@
+/// Where a command failed, filled in by the pools it went through
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub command: Option<String>,
+    pub node: Option<String>,
+    pub slot: Option<u16>,
+}
+
 #[derive(Debug)]
 pub enum RedisError {
     #[allow(clippy::enum_variant_names)]
@@ -6,6 +14,10 @@ pub enum RedisError {
     PoolError(redis::RedisError),
     NotFoundNode,
     NoSlot,
+    Timeout(std::time::Duration),
+    // type-erased to keep Python out of the pure Rust paths
+    Hook(Box<dyn std::error::Error + Send + Sync>),
+    Context(Box<RedisError>, Box<ErrorContext>),
 }
 
 impl RedisError {
@@ -15,6 +27,65 @@ impl RedisError {
             "Not initioalized pool",
         )))
     }
+
+    /// Add to the context set by the inner pools
+    pub fn with_context(self, f: impl FnOnce(&mut ErrorContext)) -> Self {
+        let (error, mut context) = match self {
+            Self::Context(error, context) => (error, context),
+            error => (Box::new(error), Box::default()),
+        };
+        f(&mut context);
+        Self::Context(error, context)
+    }
+
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Context(_, context) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without its context
+    pub fn inner(&self) -> &Self {
+        match self {
+            Self::Context(error, _) => error,
+            error => error,
+        }
+    }
+
+    /// The error returned by redis, looking through the context
+    pub fn redis_error(&self) -> Option<&redis::RedisError> {
+        match self.inner() {
+            Self::RedisError(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn into_inner(self) -> Self {
+        match self {
+            Self::Context(error, _) => *error,
+            error => error,
+        }
+    }
+
+    /// Transient failure, the command may succeed when sent again
+    pub fn is_retryable(&self) -> bool {
+        use redis::ServerErrorKind::*;
+        match self.inner() {
+            Self::RedisError(e) | Self::PoolError(e) => {
+                e.is_io_error()
+                    || e.is_connection_dropped()
+                    || e.is_connection_refusal()
+                    || e.is_timeout()
+                    || matches!(
+                        e.kind(),
+                        redis::ErrorKind::Server(TryAgain | ClusterDown | BusyLoading | MasterDown)
+                    )
+            }
+            Self::Timeout(_) => true,
+            _ => false,
+        }
+    }
 }
 
 impl From<redis::RedisError> for RedisError {
@@ -26,7 +97,8 @@ impl From<redis::RedisError> for RedisError {
 impl From<bb8::RunError<redis::RedisError>> for RedisError {
     fn from(error: bb8::RunError<redis::RedisError>) -> Self {
         match error {
-            bb8::RunError::User(err) => RedisError::PoolError(err),
+            // the connection failed, raised like the same failure on a shared connection
+            bb8::RunError::User(err) => RedisError::RedisError(err),
             bb8::RunError::TimedOut => RedisError::PoolError(redis::RedisError::from((
                 redis::ErrorKind::Io,
                 "Timed out in bb8",
@@ -47,6 +119,16 @@ impl From<tokio::sync::AcquireError> for RedisError {
     }
 }
 
+impl From<tokio::task::JoinError> for RedisError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        RedisError::PoolError(redis::RedisError::from((
+            redis::ErrorKind::Io,
+            "Task failed",
+            e.to_string(),
+        )))
+    }
+}
+
 impl From<RedisError> for redis::RedisError {
     fn from(e: RedisError) -> Self {
         match e {
@@ -56,6 +138,11 @@ impl From<RedisError> for redis::RedisError {
             }
             RedisError::NoSlot => redis::RedisError::from((redis::ErrorKind::Io, "Not found slot")),
             RedisError::PoolError(e) => e,
+            RedisError::Timeout(_) => redis::RedisError::from((redis::ErrorKind::Io, "Timed out")),
+            RedisError::Hook(e) => {
+                redis::RedisError::from((redis::ErrorKind::Io, "Hook failed", e.to_string()))
+            }
+            RedisError::Context(e, _) => (*e).into(),
             RedisError::CommandError(_) => todo!(),
         }
     }
@@ -105,3 +192,31 @@ impl From<pyo3::PyErr> for ValueError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::RedisError;
+
+    #[test]
+    fn context() {
+        let e = RedisError::RedisError(redis::RedisError::from((redis::ErrorKind::Io, "io")));
+        let e = e.with_context(|c| c.node = Some("a:6379".into()));
+        let e = e.with_context(|c| c.slot = Some(7));
+        let context = e.context().unwrap();
+        assert_eq!(context.node.as_deref(), Some("a:6379"));
+        assert_eq!(context.slot, Some(7));
+        assert!(matches!(e.inner(), RedisError::RedisError(_)));
+        assert!(e.is_retryable());
+        assert!(!RedisError::NoSlot.is_retryable());
+    }
+
+    #[test]
+    fn pool_errors() {
+        let refused =
+            redis::RedisError::from(std::io::Error::from(std::io::ErrorKind::ConnectionRefused));
+        let e = RedisError::from(bb8::RunError::User(refused));
+        assert!(e.redis_error().unwrap().is_connection_refusal());
+        let e = RedisError::from(bb8::RunError::TimedOut);
+        assert!(matches!(e, RedisError::PoolError(_)));
+    }
+}
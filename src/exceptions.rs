@@ -1,25 +1,115 @@
 use crate::error;
-use pyo3::{create_exception, PyErr};
+use pyo3::{create_exception, prelude::*};
+use redis::{ErrorKind, ServerErrorKind};
 
 create_exception!(exceptions, RedisError, pyo3::exceptions::PyException);
 create_exception!(exceptions, PoolError, pyo3::exceptions::PyException);
+create_exception!(exceptions, WatchError, RedisError);
+create_exception!(exceptions, TimeoutError, RedisError);
+create_exception!(exceptions, ConnectionError, RedisError);
+create_exception!(exceptions, AuthenticationError, ConnectionError);
+create_exception!(exceptions, ResponseError, RedisError);
+create_exception!(exceptions, WrongTypeError, ResponseError);
+create_exception!(exceptions, NoScriptError, ResponseError);
+create_exception!(exceptions, BusyLoadingError, ResponseError);
+create_exception!(exceptions, ClusterDownError, ResponseError);
+create_exception!(exceptions, MovedError, ResponseError);
+create_exception!(exceptions, AskError, ResponseError);
+create_exception!(exceptions, ReadOnlyError, ResponseError);
+
+/// Exception class matching the error kind and the server error code
+fn new_err(e: &redis::RedisError, args: String) -> PyErr {
+    match (e.kind(), e.code()) {
+        _ if e.is_timeout() => TimeoutError::new_err(args),
+        (ErrorKind::AuthenticationFailed, _) => AuthenticationError::new_err(args),
+        (_, Some("NOAUTH" | "WRONGPASS")) => AuthenticationError::new_err(args),
+        (ErrorKind::Io | ErrorKind::ClusterConnectionNotFound, _) => ConnectionError::new_err(args),
+        (ErrorKind::Server(kind), _) => match kind {
+            ServerErrorKind::NoScript => NoScriptError::new_err(args),
+            ServerErrorKind::BusyLoading => BusyLoadingError::new_err(args),
+            ServerErrorKind::ClusterDown => ClusterDownError::new_err(args),
+            ServerErrorKind::Moved => MovedError::new_err(args),
+            ServerErrorKind::Ask => AskError::new_err(args),
+            ServerErrorKind::ReadOnly => ReadOnlyError::new_err(args),
+            _ => ResponseError::new_err(args),
+        },
+        (ErrorKind::Extension, Some("WRONGTYPE")) => WrongTypeError::new_err(args),
+        (ErrorKind::Extension, _) => ResponseError::new_err(args),
+        _ if e.is_connection_dropped() || e.is_connection_refusal() => {
+            ConnectionError::new_err(args)
+        }
+        _ => RedisError::new_err(args),
+    }
+}
 
 impl From<error::RedisError> for PyErr {
     fn from(e: error::RedisError) -> Self {
         match e {
-            error::RedisError::CommandError(s) => RedisError::new_err(s),
-            error::RedisError::PoolError(e) => PoolError::new_err(e.to_string()),
-            error::RedisError::RedisError(e) => {
-                let args = match (e.code(), e.detail()) {
-                    (Some("ERR"), Some(detail)) => detail.to_string(),
-                    (Some(code), Some(detail)) => format!("{code} {detail}"),
-                    (Some(code), None) => code.to_string(),
-                    _ => e.to_string(),
-                };
-                RedisError::new_err(args)
-            }
-            error::RedisError::NotFoundNode => PoolError::new_err("Not found node".to_string()),
-            error::RedisError::NoSlot => PoolError::new_err("Not found slot".to_string()),
+            error::RedisError::Hook(e) => match e.downcast::<PyErr>() {
+                Ok(e) => *e,
+                Err(e) => RedisError::new_err(e.to_string()),
+            },
+            e => PyErr::from(&e),
+        }
+    }
+}
+
+impl From<&error::RedisError> for PyErr {
+    fn from(e: &error::RedisError) -> Self {
+        let err = exception(e.inner());
+        if !matches!(e.inner(), error::RedisError::Hook(_)) {
+            Python::attach(|py| {
+                if let Err(e) = set_attributes(py, &err, e) {
+                    e.write_unraisable(py, None);
+                }
+            });
         }
+        err
     }
 }
+
+fn exception(e: &error::RedisError) -> PyErr {
+    match e {
+        error::RedisError::CommandError(s) => RedisError::new_err(s.clone()),
+        error::RedisError::PoolError(e) => PoolError::new_err(e.to_string()),
+        error::RedisError::RedisError(e) if e.is_timeout() => new_err(e, e.to_string()),
+        error::RedisError::RedisError(e) => {
+            let args = match (e.code(), e.detail()) {
+                (Some("ERR"), Some(detail)) => detail.to_string(),
+                (Some(code), Some(detail)) => format!("{code} {detail}"),
+                (Some(code), None) => code.to_string(),
+                _ => e.to_string(),
+            };
+            new_err(e, args)
+        }
+        error::RedisError::NotFoundNode => PoolError::new_err("Not found node".to_string()),
+        error::RedisError::NoSlot => PoolError::new_err("Not found slot".to_string()),
+        error::RedisError::Timeout(timeout) => {
+            TimeoutError::new_err(format!("Timed out after {timeout:?}"))
+        }
+        error::RedisError::Hook(e) => match e.downcast_ref::<PyErr>() {
+            Some(e) => Python::attach(|py| e.clone_ref(py)),
+            None => RedisError::new_err(e.to_string()),
+        },
+        error::RedisError::Context(e, _) => exception(e),
+    }
+}
+
+/// Structured `code`, `detail`, `command`, `node`, `slot` and `retryable` attributes
+fn set_attributes(py: Python, err: &PyErr, e: &error::RedisError) -> PyResult<()> {
+    let value = err.value(py);
+    let (code, detail) = match e.inner() {
+        error::RedisError::RedisError(e) | error::RedisError::PoolError(e) => {
+            (e.code(), e.detail())
+        }
+        _ => (None, None),
+    };
+    let context = e.context().cloned().unwrap_or_default();
+    value.setattr("code", code)?;
+    value.setattr("detail", detail)?;
+    value.setattr("command", context.command)?;
+    value.setattr("node", context.node)?;
+    value.setattr("slot", context.slot)?;
+    value.setattr("retryable", e.is_retryable())?;
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! RESP server replying through a handler, standing in for redis and sentinel nodes in tests
+
+use tokio::net::{TcpListener, TcpStream};
+
+/// Split the first `\r\n` terminated line off the buffer
+fn line(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = buf.windows(2).position(|w| w == b"\r\n")?;
+    Some((&buf[..end], &buf[end + 2..]))
+}
+
+fn number(line: &[u8]) -> Option<usize> {
+    std::str::from_utf8(line).ok()?.parse().ok()
+}
+
+/// Parse one command sent as an array of bulk strings, with the bytes it took
+fn parse(buf: &[u8]) -> Option<(Vec<Vec<u8>>, usize)> {
+    let (head, mut rest) = line(buf.strip_prefix(b"*")?)?;
+    let mut args = vec![];
+    for _ in 0..number(head)? {
+        let (len, tail) = line(rest.strip_prefix(b"$")?)?;
+        let len = number(len)?;
+        if tail.len() < len + 2 {
+            return None;
+        }
+        args.push(tail[..len].to_vec());
+        rest = &tail[len + 2..];
+    }
+    Some((args, buf.len() - rest.len()))
+}
+
+async fn write_all(socket: &TcpStream, mut data: &[u8]) -> std::io::Result<()> {
+    while !data.is_empty() {
+        socket.writable().await?;
+        match socket.try_write(data) {
+            Ok(n) => data = &data[n..],
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Listen on a local port, answering every command with the RESP reply of the handler.
+/// An empty reply closes the connection
+pub async fn serve<F>(handler: F) -> String
+where
+    F: Fn(&[Vec<u8>]) -> Vec<u8> + Clone + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let mut buf = vec![];
+                let mut chunk = [0; 4096];
+                while socket.readable().await.is_ok() {
+                    let n = match socket.try_read(&mut chunk) {
+                        Ok(0) => return,
+                        Ok(n) => n,
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                        Err(_) => return,
+                    };
+                    buf.extend_from_slice(&chunk[..n]);
+                    while let Some((cmd, len)) = parse(&buf) {
+                        buf.drain(..len);
+                        let reply = handler(&cmd);
+                        if reply.is_empty() || write_all(&socket, &reply).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Upper-cased command name
+pub fn name(cmd: &[Vec<u8>]) -> String {
+    String::from_utf8_lossy(&cmd[0]).to_ascii_uppercase()
+}
+
+pub fn ok() -> Vec<u8> {
+    b"+OK\r\n".to_vec()
+}
+
+pub fn bulk(value: &[u8]) -> Vec<u8> {
+    let mut reply = format!("${}\r\n", value.len()).into_bytes();
+    reply.extend_from_slice(value);
+    reply.extend_from_slice(b"\r\n");
+    reply
+}
+
+pub fn error(message: &str) -> Vec<u8> {
+    format!("-{message}\r\n").into_bytes()
+}
+
+/// SENTINEL GET-MASTER-ADDR-BY-NAME reply for a `host:port` address
+pub fn master(addr: &str) -> Vec<u8> {
+    let (host, port) = addr.rsplit_once(':').unwrap();
+    let mut reply = b"*2\r\n".to_vec();
+    reply.extend(bulk(host.as_bytes()));
+    reply.extend(bulk(port.as_bytes()));
+    reply
+}
+
+/// CLUSTER SLOTS reply of one shard owning every slot, node ids are the addresses
+pub fn slots(master: &str, replicas: &[&str]) -> Vec<u8> {
+    let mut reply = format!("*1\r\n*{}\r\n:0\r\n:16383\r\n", 3 + replicas.len()).into_bytes();
+    for addr in [master].iter().chain(replicas) {
+        let (host, port) = addr.rsplit_once(':').unwrap();
+        reply.extend(b"*3\r\n");
+        reply.extend(bulk(host.as_bytes()));
+        reply.extend(format!(":{port}\r\n").into_bytes());
+        reply.extend(bulk(addr.as_bytes()));
+    }
+    reply
+}
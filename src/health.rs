@@ -0,0 +1,86 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use redis::Value;
+
+#[derive(Default)]
+struct State {
+    last_error: Option<String>,
+    last_ping: Option<SystemTime>,
+}
+
+/// Result of the background PINGs of a node
+pub struct Health {
+    threshold: usize,
+    failures: AtomicUsize,
+    state: Mutex<State>,
+}
+
+impl Health {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            failures: AtomicUsize::new(0),
+            state: Default::default(),
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.failures.load(Ordering::Acquire) < self.threshold
+    }
+
+    pub fn success(&self) {
+        self.failures.store(0, Ordering::Release);
+        self.state.lock().unwrap().last_ping = Some(SystemTime::now());
+    }
+
+    /// Record a failed PING, returns true when the node is unhealthy
+    pub fn failure(&self, error: String) -> bool {
+        self.state.lock().unwrap().last_error = Some(error);
+        self.failures.fetch_add(1, Ordering::AcqRel) + 1 >= self.threshold
+    }
+
+    pub fn status(&self) -> Value {
+        let state = self.state.lock().unwrap();
+        let last_ping = state
+            .last_ping
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map_or(Value::Nil, |t| Value::Double(t.as_secs_f64()));
+        let last_error = state
+            .last_error
+            .clone()
+            .map_or(Value::Nil, Value::SimpleString);
+        let failures = self.failures.load(Ordering::Acquire) as i64;
+        Value::Map(vec![
+            (
+                Value::SimpleString("healthy".into()),
+                Value::Boolean(self.is_healthy()),
+            ),
+            (Value::SimpleString("failures".into()), Value::Int(failures)),
+            (Value::SimpleString("last_error".into()), last_error),
+            (Value::SimpleString("last_ping".into()), last_ping),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Health;
+
+    #[test]
+    fn threshold() {
+        let health = Health::new(2);
+        assert!(health.is_healthy());
+        assert!(!health.failure("io".into()));
+        assert!(health.is_healthy());
+        assert!(health.failure("io".into()));
+        assert!(!health.is_healthy());
+        health.success();
+        assert!(health.is_healthy());
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use pyo3::prelude::*;
+use redis::{Cmd, Value};
+
+use crate::{error, types};
+
+/// Command seen by hooks, `args` may be rewritten before it is sent
+#[pyclass(from_py_object)]
+#[derive(Clone, Debug)]
+pub struct Call {
+    #[pyo3(get)]
+    pub name: String,
+    pub args: Vec<Vec<u8>>,
+    #[pyo3(get)]
+    pub keys: Vec<Vec<u8>>,
+    #[pyo3(get)]
+    pub node: Option<String>,
+    rewritten: bool,
+}
+
+impl Call {
+    pub fn new(cmd: &Cmd, keys: &[Vec<u8>], node: Option<String>) -> Self {
+        let mut args = cmd.args_iter().filter_map(|arg| match arg {
+            redis::Arg::Simple(arg) => Some(arg.to_vec()),
+            _ => None,
+        });
+        let name = args.next().unwrap_or_default();
+        Self {
+            name: String::from_utf8_lossy(&name).to_ascii_uppercase(),
+            args: args.collect(),
+            keys: keys.to_vec(),
+            node,
+            rewritten: false,
+        }
+    }
+
+    pub fn set_args(&mut self, args: Vec<Vec<u8>>) {
+        self.args = args;
+        self.rewritten = true;
+    }
+
+    /// The command to send when a hook rewrote the arguments
+    pub fn rewritten(&self) -> Option<Cmd> {
+        self.rewritten
+            .then(|| redis::cmd(&self.name).arg(&self.args).to_owned())
+    }
+}
+
+#[pymethods]
+impl Call {
+    #[getter]
+    fn args(&self) -> Vec<Vec<u8>> {
+        self.args.clone()
+    }
+
+    #[setter(args)]
+    fn py_set_args(&mut self, args: Vec<types::Arg>) {
+        self.set_args(args.iter().map(types::Arg::to_vec).collect());
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Call({} {:?} node={:?})", self.name, self.args, self.node)
+    }
+}
+
+#[async_trait]
+pub trait Hook: Send + Sync {
+    /// Called before the command is sent, a returned value is used as the reply instead
+    async fn before(&self, _call: &mut Call) -> Result<Option<Value>, error::RedisError> {
+        Ok(None)
+    }
+
+    /// Called with the outcome of the command
+    async fn after(
+        &self,
+        _call: &Call,
+        _elapsed: Duration,
+        _result: &Result<Value, error::RedisError>,
+    ) {
+    }
+
+    /// Python object the hook was registered with
+    fn object(&self) -> Option<&Py<PyAny>> {
+        None
+    }
+}
+
+/// Python object with optional `before(call)` and `after(call, duration, error)` methods,
+/// called synchronously with the GIL held
+pub struct PyHook(Py<PyAny>);
+
+impl PyHook {
+    pub fn new(py: Python, hook: Py<PyAny>) -> PyResult<Self> {
+        let inspect = py.import("inspect")?;
+        for name in ["before", "after"] {
+            let Ok(method) = hook.bind(py).getattr(name) else {
+                continue;
+            };
+            if inspect
+                .call_method1("iscoroutinefunction", (method,))?
+                .is_truthy()?
+            {
+                return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                    "Hook method {name} must be synchronous"
+                )));
+            }
+        }
+        Ok(Self(hook))
+    }
+}
+
+#[async_trait]
+impl Hook for PyHook {
+    async fn before(&self, call: &mut Call) -> Result<Option<Value>, error::RedisError> {
+        Python::attach(|py| {
+            let hook = self.0.bind(py);
+            if !hook.hasattr("before")? {
+                return Ok(None);
+            }
+            let arg = Bound::new(py, call.clone())?;
+            let result = hook.call_method1("before", (&arg,))?;
+            *call = arg.borrow().clone();
+            if result.is_none() {
+                Ok(None)
+            } else {
+                types::to_value(&result).map(Some)
+            }
+        })
+        .map_err(|e| error::RedisError::Hook(Box::new(e)))
+    }
+
+    async fn after(
+        &self,
+        call: &Call,
+        elapsed: Duration,
+        result: &Result<Value, error::RedisError>,
+    ) {
+        Python::attach(|py| {
+            let hook = self.0.bind(py);
+            let error = match result {
+                Err(e) => PyErr::from(e).into_value(py).into_any(),
+                Ok(Value::ServerError(e)) => {
+                    let e = error::RedisError::RedisError(e.clone().into());
+                    PyErr::from(e).into_value(py).into_any()
+                }
+                Ok(_) => py.None(),
+            };
+            let result = hook.hasattr("after").and_then(|after| match after {
+                true => hook
+                    .call_method1("after", (call.clone(), elapsed.as_secs_f64(), error))
+                    .map(|_| ()),
+                false => Ok(()),
+            });
+            if let Err(e) = result {
+                e.write_unraisable(py, Some(hook));
+            }
+        })
+    }
+
+    fn object(&self) -> Option<&Py<PyAny>> {
+        Some(&self.0)
+    }
+}
+
+/// Hooks run in registration order around every command
+#[derive(Default)]
+pub struct Hooks {
+    hooks: RwLock<Vec<Arc<dyn Hook>>>,
+}
+
+impl Hooks {
+    pub fn add(&self, hook: Arc<dyn Hook>) {
+        self.hooks.write().unwrap().push(hook);
+    }
+
+    /// Remove the hooks registered with the Python object
+    pub fn remove(&self, py: Python, object: &Py<PyAny>) -> bool {
+        let mut hooks = self.hooks.write().unwrap();
+        let len = hooks.len();
+        hooks.retain(|hook| {
+            hook.object()
+                .is_none_or(|o| !o.bind(py).is(object.bind(py)))
+        });
+        hooks.len() != len
+    }
+
+    pub fn get(&self) -> Vec<Arc<dyn Hook>> {
+        self.hooks.read().unwrap().clone()
+    }
+}
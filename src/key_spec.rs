@@ -0,0 +1,528 @@
+use std::{
+    collections::HashMap,
+    sync::{OnceLock, RwLock},
+};
+
+use redis::{FromRedisValue, Value};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Begin {
+    Index(usize),
+    Keyword(Vec<u8>, i64),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Find {
+    Range {
+        lastkey: i64,
+        step: usize,
+        limit: usize,
+    },
+    KeyNum {
+        keynumidx: usize,
+        firstkey: usize,
+        step: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct KeySpec {
+    begin: Begin,
+    find: Find,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+enum Block {
+    #[default]
+    Never,
+    Always,
+    Keyword(Vec<u8>),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct CommandSpec {
+    specs: Vec<KeySpec>,
+    block: Block,
+}
+
+fn range(index: usize, lastkey: i64, step: usize) -> KeySpec {
+    KeySpec {
+        begin: Begin::Index(index),
+        find: Find::Range {
+            lastkey,
+            step,
+            limit: 0,
+        },
+    }
+}
+
+fn keynum(index: usize, keynumidx: usize) -> KeySpec {
+    KeySpec {
+        begin: Begin::Index(index),
+        find: Find::KeyNum {
+            keynumidx,
+            firstkey: keynumidx + 1,
+            step: 1,
+        },
+    }
+}
+
+fn keyword(keyword: &[u8], startfrom: i64, lastkey: i64, limit: usize) -> KeySpec {
+    KeySpec {
+        begin: Begin::Keyword(keyword.to_vec(), startfrom),
+        find: Find::Range {
+            lastkey,
+            step: 1,
+            limit,
+        },
+    }
+}
+
+fn spec(specs: Vec<KeySpec>) -> CommandSpec {
+    CommandSpec {
+        specs,
+        block: Block::Never,
+    }
+}
+
+fn blocking(specs: Vec<KeySpec>) -> CommandSpec {
+    CommandSpec {
+        specs,
+        block: Block::Always,
+    }
+}
+
+/// Fallback table for servers without COMMAND, e.g. the mock
+fn bundled() -> HashMap<Vec<u8>, CommandSpec> {
+    let all = || spec(vec![range(1, -1, 1)]);
+    let streams = |block| CommandSpec {
+        specs: vec![keyword(b"STREAMS", 1, -1, 2)],
+        block,
+    };
+    let table = [
+        ("INFO", spec(vec![])),
+        ("CLIENT", spec(vec![])),
+        ("KEYS", spec(vec![])),
+        ("SCAN", spec(vec![])),
+        ("DBSIZE", spec(vec![])),
+        ("CLUSTER|KEYSLOT", spec(vec![range(2, 0, 1)])),
+        ("MGET", all()),
+        ("DEL", all()),
+        ("EXISTS", all()),
+        ("UNLINK", all()),
+        ("TOUCH", all()),
+        ("WATCH", all()),
+        ("PFCOUNT", all()),
+        ("PFMERGE", all()),
+        ("SINTER", all()),
+        ("SUNION", all()),
+        ("SDIFF", all()),
+        ("MSET", spec(vec![range(1, -1, 2)])),
+        ("MSETNX", spec(vec![range(1, -1, 2)])),
+        ("XGROUP", spec(vec![range(2, 0, 1)])),
+        ("XINFO", spec(vec![range(2, 0, 1)])),
+        ("OBJECT", spec(vec![range(2, 0, 1)])),
+        ("MEMORY|USAGE", spec(vec![range(2, 0, 1)])),
+        (
+            "SORT",
+            spec(vec![range(1, 0, 1), keyword(b"STORE", 1, 0, 0)]),
+        ),
+        ("ZMPOP", spec(vec![keynum(1, 0)])),
+        ("LMPOP", spec(vec![keynum(1, 0)])),
+        ("ZDIFF", spec(vec![keynum(1, 0)])),
+        ("ZINTER", spec(vec![keynum(1, 0)])),
+        ("ZINTERCARD", spec(vec![keynum(1, 0)])),
+        ("ZUNION", spec(vec![keynum(1, 0)])),
+        ("SINTERCARD", spec(vec![keynum(1, 0)])),
+        ("ZDIFFSTORE", spec(vec![range(1, 0, 1), keynum(2, 0)])),
+        ("ZINTERSTORE", spec(vec![range(1, 0, 1), keynum(2, 0)])),
+        ("ZUNIONSTORE", spec(vec![range(1, 0, 1), keynum(2, 0)])),
+        ("EVAL", spec(vec![keynum(2, 0)])),
+        ("EVALSHA", spec(vec![keynum(2, 0)])),
+        ("EVAL_RO", spec(vec![keynum(2, 0)])),
+        ("EVALSHA_RO", spec(vec![keynum(2, 0)])),
+        ("FCALL", spec(vec![keynum(2, 0)])),
+        ("FCALL_RO", spec(vec![keynum(2, 0)])),
+        ("BLPOP", blocking(vec![range(1, -2, 1)])),
+        ("BRPOP", blocking(vec![range(1, -2, 1)])),
+        ("BZPOPMIN", blocking(vec![range(1, -2, 1)])),
+        ("BZPOPMAX", blocking(vec![range(1, -2, 1)])),
+        ("BLMPOP", blocking(vec![keynum(2, 0)])),
+        ("BZMPOP", blocking(vec![keynum(2, 0)])),
+        ("BLMOVE", blocking(vec![range(1, 1, 1)])),
+        ("XREAD", streams(Block::Keyword(b"BLOCK".to_vec()))),
+        ("XREADGROUP", streams(Block::Keyword(b"BLOCK".to_vec()))),
+        (
+            "MIGRATE",
+            spec(vec![range(3, 0, 1), keyword(b"KEYS", -2, -1, 0)]),
+        ),
+    ];
+    table
+        .into_iter()
+        .map(|(name, spec)| (name.as_bytes().to_vec(), spec))
+        .collect()
+}
+
+fn bundled_table() -> &'static HashMap<Vec<u8>, CommandSpec> {
+    static TABLE: OnceLock<HashMap<Vec<u8>, CommandSpec>> = OnceLock::new();
+    TABLE.get_or_init(bundled)
+}
+
+fn to_map(value: Value) -> HashMap<String, Value> {
+    FromRedisValue::from_redis_value(value).unwrap_or_default()
+}
+
+fn to_int(value: Option<Value>) -> i64 {
+    value
+        .and_then(|v| i64::from_redis_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn parse_key_spec(value: Value) -> Option<KeySpec> {
+    let mut value = to_map(value);
+    let mut begin = to_map(value.remove("begin_search")?);
+    let mut begin_spec = to_map(begin.remove("spec")?);
+    let begin = match String::from_redis_value(begin.remove("type")?)
+        .ok()?
+        .as_str()
+    {
+        "index" => Begin::Index(to_int(begin_spec.remove("index")) as usize),
+        "keyword" => Begin::Keyword(
+            Vec::<u8>::from_redis_value(begin_spec.remove("keyword")?)
+                .ok()?
+                .to_ascii_uppercase(),
+            to_int(begin_spec.remove("startfrom")),
+        ),
+        _ => return None,
+    };
+    let mut find = to_map(value.remove("find_keys")?);
+    let mut find_spec = to_map(find.remove("spec")?);
+    let find = match String::from_redis_value(find.remove("type")?)
+        .ok()?
+        .as_str()
+    {
+        "range" => Find::Range {
+            lastkey: to_int(find_spec.remove("lastkey")),
+            step: to_int(find_spec.remove("keystep")).max(1) as usize,
+            limit: to_int(find_spec.remove("limit")) as usize,
+        },
+        "keynum" => Find::KeyNum {
+            keynumidx: to_int(find_spec.remove("keynumidx")) as usize,
+            firstkey: to_int(find_spec.remove("firstkey")) as usize,
+            step: to_int(find_spec.remove("keystep")).max(1) as usize,
+        },
+        _ => return None,
+    };
+    Some(KeySpec { begin, find })
+}
+
+fn parse_command(value: Value, table: &mut HashMap<Vec<u8>, CommandSpec>) -> Option<()> {
+    let mut fields = Vec::<Value>::from_redis_value(value).ok()?.into_iter();
+    let name = Vec::<u8>::from_redis_value(fields.next()?)
+        .ok()?
+        .to_ascii_uppercase();
+    let _arity = fields.next();
+    let flags: Vec<String> = FromRedisValue::from_redis_value(fields.next()?).unwrap_or_default();
+    let first = to_int(fields.next());
+    let last = to_int(fields.next());
+    let step = to_int(fields.next()).max(1) as usize;
+    let _acl = fields.next();
+    let _tips = fields.next();
+    let key_specs: Option<Vec<Value>> = fields
+        .next()
+        .and_then(|v| FromRedisValue::from_redis_value(v).ok());
+    let subcommands: Vec<Value> = fields
+        .next()
+        .and_then(|v| FromRedisValue::from_redis_value(v).ok())
+        .unwrap_or_default();
+
+    let specs = match key_specs {
+        Some(specs) => specs.into_iter().filter_map(parse_key_spec).collect(),
+        // Legacy first/last/step for servers without key specs
+        None if first > 0 => {
+            let lastkey = if last < 0 { last } else { last - first };
+            vec![range(first as usize, lastkey, step)]
+        }
+        None => vec![],
+    };
+    let block = match name.as_slice() {
+        b"XREAD" | b"XREADGROUP" => Block::Keyword(b"BLOCK".to_vec()),
+        _ if flags.iter().any(|f| f.eq_ignore_ascii_case("blocking")) => Block::Always,
+        _ => Block::Never,
+    };
+    for sub in subcommands.into_iter() {
+        parse_command(sub, table);
+    }
+    table.insert(name, CommandSpec { specs, block });
+    Some(())
+}
+
+/// Key specs loaded from the COMMAND reply of a server, the bundled table fills the gaps
+#[derive(Default)]
+pub struct KeySpecs {
+    table: RwLock<HashMap<Vec<u8>, CommandSpec>>,
+}
+
+impl KeySpecs {
+    pub fn is_loaded(&self) -> bool {
+        !self.table.read().unwrap().is_empty()
+    }
+
+    /// Load key specs from a COMMAND reply
+    pub fn load(&self, value: Value) -> usize {
+        let Ok(commands) = Vec::<Value>::from_redis_value(value) else {
+            return 0;
+        };
+        let mut loaded = HashMap::new();
+        for command in commands.into_iter() {
+            parse_command(command, &mut loaded);
+        }
+        let count = loaded.len();
+        self.table.write().unwrap().extend(loaded);
+        count
+    }
+
+    /// Keys and blocking flag of a command; falls back to the first argument for unknown commands
+    pub fn extract(&self, args: &[&[u8]]) -> (Vec<Vec<u8>>, bool) {
+        extract_from(&self.table.read().unwrap(), args)
+    }
+}
+
+impl CommandSpec {
+    fn keys(&self, args: &[&[u8]]) -> Vec<Vec<u8>> {
+        let len = args.len();
+        let mut result = vec![];
+        for spec in self.specs.iter() {
+            let begin = match spec.begin {
+                Begin::Index(i) => i,
+                Begin::Keyword(ref kw, startfrom) => {
+                    let is_kw = |i: &usize| args[*i].eq_ignore_ascii_case(kw);
+                    let found = if startfrom >= 0 {
+                        (startfrom as usize..len).find(is_kw)
+                    } else {
+                        let end = (len as i64 + startfrom + 1).max(1) as usize;
+                        (1..end).rev().find(is_kw)
+                    };
+                    match found {
+                        Some(i) => i + 1,
+                        None => continue,
+                    }
+                }
+            };
+            if begin >= len {
+                continue;
+            }
+            match spec.find {
+                Find::Range {
+                    lastkey,
+                    step,
+                    limit,
+                } => {
+                    // a limit keeps 1/limit of the remaining args, counted back by lastkey
+                    let last = match (len - begin).checked_div(limit) {
+                        _ if lastkey >= 0 => begin as i64 + lastkey,
+                        Some(count) => begin as i64 + count as i64 + lastkey,
+                        None => len as i64 + lastkey,
+                    };
+                    let last = last.max(begin as i64) as usize;
+                    for i in (begin..=last.min(len - 1)).step_by(step) {
+                        result.push(args[i].to_vec());
+                    }
+                }
+                Find::KeyNum {
+                    keynumidx,
+                    firstkey,
+                    step,
+                } => {
+                    let numkeys = args
+                        .get(begin + keynumidx)
+                        .and_then(|n| String::from_utf8_lossy(n).parse::<usize>().ok())
+                        .unwrap_or_default();
+                    let first = begin + firstkey;
+                    for i in (0..numkeys).map(|n| first + n * step) {
+                        if let Some(key) = args.get(i) {
+                            result.push(key.to_vec());
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn block(&self, args: &[&[u8]]) -> bool {
+        match self.block {
+            Block::Never => false,
+            Block::Always => true,
+            Block::Keyword(ref kw) => args.iter().any(|a| a.eq_ignore_ascii_case(kw)),
+        }
+    }
+}
+
+/// Keys and blocking flag of a command from the bundled table
+pub fn extract(args: &[&[u8]]) -> (Vec<Vec<u8>>, bool) {
+    extract_from(&HashMap::new(), args)
+}
+
+fn extract_from(loaded: &HashMap<Vec<u8>, CommandSpec>, args: &[&[u8]]) -> (Vec<Vec<u8>>, bool) {
+    let Some(name) = args.first() else {
+        return (vec![], false);
+    };
+    let name = name.to_ascii_uppercase();
+    let get = |name: &[u8]| loaded.get(name).or_else(|| bundled_table().get(name));
+    let spec = args
+        .get(1)
+        .and_then(|sub| {
+            let mut full = name.clone();
+            full.push(b'|');
+            full.extend(sub.to_ascii_uppercase());
+            get(&full)
+        })
+        .or_else(|| get(&name));
+    match spec {
+        Some(spec) => (spec.keys(args), spec.block(args)),
+        None => (
+            args.get(1).map(|k| vec![k.to_vec()]).unwrap_or_default(),
+            false,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::Value;
+
+    use super::{extract, Begin, CommandSpec, Find, KeySpec, KeySpecs};
+
+    fn keys(args: &[&str]) -> Vec<String> {
+        let args: Vec<&[u8]> = args.iter().map(|a| a.as_bytes()).collect();
+        extract(&args)
+            .0
+            .into_iter()
+            .map(|k| String::from_utf8(k).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn bundled_keys() {
+        assert_eq!(keys(&["GET", "a"]), vec!["a"]);
+        assert_eq!(keys(&["MSET", "a", "1", "b", "2"]), vec!["a", "b"]);
+        assert_eq!(keys(&["OBJECT", "ENCODING", "a"]), vec!["a"]);
+        assert_eq!(keys(&["MEMORY", "USAGE", "a"]), vec!["a"]);
+        assert_eq!(
+            keys(&["SORT", "a", "LIMIT", "0", "1", "STORE", "b"]),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            keys(&["EVAL", "return 1", "2", "a", "b", "c"]),
+            vec!["a", "b"]
+        );
+        assert_eq!(keys(&["BLPOP", "a", "b", "0"]), vec!["a", "b"]);
+        assert_eq!(
+            keys(&["XREAD", "COUNT", "1", "STREAMS", "a", "b", "0", "0"]),
+            vec!["a", "b"]
+        );
+        assert!(keys(&["INFO", "SERVER"]).is_empty());
+    }
+
+    #[test]
+    fn bundled_block() {
+        let args: Vec<&[u8]> = vec![b"XREAD", b"STREAMS", b"a", b"0"];
+        assert!(!extract(&args).1);
+        let args: Vec<&[u8]> = vec![b"XREAD", b"BLOCK", b"0", b"STREAMS", b"a", b"0"];
+        assert!(extract(&args).1);
+    }
+
+    #[test]
+    fn load_command_info() {
+        let spec = |begin: Value, find: Value| {
+            Value::Array(vec![
+                Value::SimpleString("begin_search".into()),
+                begin,
+                Value::SimpleString("find_keys".into()),
+                find,
+            ])
+        };
+        let kv = |pairs: Vec<(&str, Value)>| {
+            Value::Array(
+                pairs
+                    .into_iter()
+                    .flat_map(|(k, v)| vec![Value::SimpleString(k.into()), v])
+                    .collect(),
+            )
+        };
+        let command = Value::Array(vec![
+            Value::BulkString(b"copyto".to_vec()),
+            Value::Int(-3),
+            Value::Array(vec![Value::SimpleString("write".into())]),
+            Value::Int(1),
+            Value::Int(2),
+            Value::Int(1),
+            Value::Array(vec![]),
+            Value::Array(vec![]),
+            Value::Array(vec![spec(
+                kv(vec![
+                    ("type", Value::SimpleString("keyword".into())),
+                    (
+                        "spec",
+                        kv(vec![
+                            ("keyword", Value::BulkString(b"TO".to_vec())),
+                            ("startfrom", Value::Int(1)),
+                        ]),
+                    ),
+                ]),
+                kv(vec![
+                    ("type", Value::SimpleString("range".into())),
+                    (
+                        "spec",
+                        kv(vec![
+                            ("lastkey", Value::Int(0)),
+                            ("keystep", Value::Int(1)),
+                            ("limit", Value::Int(0)),
+                        ]),
+                    ),
+                ]),
+            )]),
+            Value::Array(vec![]),
+        ]);
+        let specs = KeySpecs::default();
+        assert!(!specs.is_loaded());
+        assert_eq!(specs.load(Value::Array(vec![command])), 1);
+        assert!(specs.is_loaded());
+        let args: Vec<&[u8]> = vec![b"COPYTO", b"x", b"TO", b"b"];
+        assert_eq!(specs.extract(&args).0, vec![b"b".to_vec()]);
+        // other clients and the bundled table do not see the loaded specs
+        assert_eq!(KeySpecs::default().extract(&args).0, vec![b"x".to_vec()]);
+        assert_eq!(keys(&["COPYTO", "x", "TO", "b"]), vec!["x"]);
+        assert_eq!(specs.extract(&[b"GET", b"a"]).0, vec![b"a".to_vec()]);
+    }
+
+    #[test]
+    fn range_limit() {
+        let keys = |lastkey, limit, args: &[&str]| {
+            let spec = CommandSpec {
+                specs: vec![KeySpec {
+                    begin: Begin::Index(1),
+                    find: Find::Range {
+                        lastkey,
+                        step: 1,
+                        limit,
+                    },
+                }],
+                ..Default::default()
+            };
+            let args: Vec<&[u8]> = args.iter().map(|a| a.as_bytes()).collect();
+            spec.keys(&args)
+                .into_iter()
+                .map(|k| String::from_utf8(k).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let args = ["CMD", "a", "b", "c", "1", "2", "3"];
+        assert_eq!(keys(-1, 2, &args), vec!["a", "b", "c"]);
+        assert_eq!(keys(-1, 3, &args), vec!["a", "b"]);
+        assert_eq!(keys(-2, 2, &args), vec!["a", "b"]);
+        assert_eq!(keys(-1, 0, &args), args[1..].to_vec());
+        assert_eq!(keys(-2, 0, &args), args[1..6].to_vec());
+        assert_eq!(keys(-1, 2, &["CMD", "a"]), vec!["a"]);
+    }
+}
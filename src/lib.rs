@@ -1,21 +1,39 @@
+use std::collections::HashMap;
+
 use config::Config;
 use pyo3::prelude::*;
 use redis::IntoConnectionInfo;
+mod cache;
 mod client_async;
 mod client_result;
 mod client_result_async;
 mod cluster_async;
 mod cluster_bb8;
+mod codecs;
 mod command;
 mod config;
 mod error;
 mod exceptions;
+#[cfg(test)]
+mod fake;
+mod health;
+mod hooks;
+mod key_spec;
+mod metrics;
 mod mock;
 mod node;
+mod pipeline;
 mod pool;
 mod pool_manager;
+mod pubsub;
+mod retry;
+mod scan;
+mod script;
+mod sentinel;
 mod shards;
 mod shards_async;
+mod stream;
+mod transaction;
 mod types;
 
 #[pyfunction]
@@ -31,6 +49,26 @@ mod types;
     client_id=None,
     max_delay=None,
     features=None,
+    protocol=None,
+    tls=None,
+    tls_ca_certs=None,
+    tls_certfile=None,
+    tls_keyfile=None,
+    tls_insecure=false,
+    sentinels=None,
+    service_name=None,
+    read_from=None,
+    max_redirects=None,
+    client_cache=None,
+    retry_attempts=None,
+    retry_backoff=None,
+    retry_max_backoff=None,
+    retry_on=None,
+    retry_writes=false,
+    connect_timeout=None,
+    response_timeout=None,
+    health_check_interval=None,
+    health_check_failures=None,
 ))]
 #[allow(clippy::too_many_arguments)]
 fn create_client(
@@ -45,6 +83,26 @@ fn create_client(
     client_id: Option<String>,
     max_delay: Option<u64>,
     features: Option<Vec<String>>,
+    protocol: Option<u8>,
+    tls: Option<bool>,
+    tls_ca_certs: Option<String>,
+    tls_certfile: Option<String>,
+    tls_keyfile: Option<String>,
+    tls_insecure: bool,
+    sentinels: Option<Vec<String>>,
+    service_name: Option<String>,
+    read_from: Option<String>,
+    max_redirects: Option<usize>,
+    client_cache: Option<HashMap<String, f64>>,
+    retry_attempts: Option<usize>,
+    retry_backoff: Option<f64>,
+    retry_max_backoff: Option<f64>,
+    retry_on: Option<Vec<String>>,
+    retry_writes: bool,
+    connect_timeout: Option<f64>,
+    response_timeout: Option<f64>,
+    health_check_interval: Option<f64>,
+    health_check_failures: Option<usize>,
 ) -> PyResult<client_async::Client> {
     let mut nodes = initial_nodes.clone();
     if nodes.is_empty() {
@@ -73,6 +131,13 @@ fn create_client(
         cluster,
         client_id: client_id.unwrap_or_default(),
         max_delay,
+        max_redirects,
+        connect_timeout: connect_timeout
+            .map(|t| types::duration("connect_timeout", t))
+            .transpose()?,
+        response_timeout: response_timeout
+            .map(|t| types::duration("response_timeout", t))
+            .transpose()?,
         ..Default::default()
     };
 
@@ -80,10 +145,96 @@ fn create_client(
         cfg.set_features(features);
     }
 
+    if let Some(service_name) = service_name {
+        cfg.set_sentinels(sentinels.unwrap_or_default(), service_name)
+            .map_err(error::RedisError::from)?;
+    }
+
+    if let Some(ref read_from) = read_from {
+        cfg.read_from = read_from
+            .as_str()
+            .try_into()
+            .map_err(error::RedisError::from)?;
+    }
+
+    if let Some(client_cache) = client_cache {
+        cfg.set_client_cache(client_cache)
+            .map_err(error::RedisError::from)?;
+    }
+
+    if let Some(retry_attempts) = retry_attempts {
+        cfg.retry.max_attempts = retry_attempts.max(1);
+    }
+    if let Some(retry_backoff) = retry_backoff {
+        cfg.retry.backoff = types::duration("retry_backoff", retry_backoff.max(0.0))?;
+    }
+    if let Some(retry_max_backoff) = retry_max_backoff {
+        cfg.retry.max_backoff = types::duration("retry_max_backoff", retry_max_backoff.max(0.0))?;
+    }
+    if let Some(ref retry_on) = retry_on {
+        cfg.retry.retry_on = retry_on
+            .iter()
+            .map(|s| s.as_str().try_into())
+            .collect::<Result<_, _>>()
+            .map_err(error::RedisError::from)?;
+    }
+    cfg.retry.retry_writes = retry_writes;
+
+    if let Some(interval) = health_check_interval {
+        let invalid = |msg: String| pyo3::exceptions::PyValueError::new_err(msg);
+        if !(interval.is_finite() && interval > 0.0) {
+            return Err(invalid(format!(
+                "health_check_interval must be a finite number of seconds > 0, got {interval}"
+            )));
+        }
+        let failures = health_check_failures.unwrap_or(3);
+        if failures == 0 {
+            return Err(invalid("health_check_failures must be > 0".to_string()));
+        }
+        cfg.health_check = Some(config::HealthCheck {
+            interval: types::duration("health_check_interval", interval)?,
+            failures,
+        });
+    }
+
+    if let Some(protocol) = protocol {
+        cfg.set_protocol(protocol)
+            .map_err(error::RedisError::from)?;
+    }
+
+    let tls = tls.unwrap_or(
+        tls_insecure || tls_ca_certs.is_some() || tls_certfile.is_some() || tls_keyfile.is_some(),
+    );
+    if tls {
+        let tls = config::Tls {
+            ca_certs: tls_ca_certs.map(std::fs::read).transpose()?,
+            client_cert: tls_certfile.map(std::fs::read).transpose()?,
+            client_key: tls_keyfile.map(std::fs::read).transpose()?,
+            insecure: tls_insecure,
+        };
+        cfg.set_tls(tls).map_err(error::RedisError::from)?;
+    }
+
     let cm = pool_manager::PoolManager::new(cfg)?;
     Ok(cm.into())
 }
 
+/// Register a codec usable as `encoding=`, `encode` is required to serialize arguments
+#[pyfunction]
+#[pyo3(signature = (name, decode, encode=None))]
+fn register_codec(name: String, decode: Py<PyAny>, encode: Option<Py<PyAny>>) -> PyResult<()> {
+    if codecs::BUILTIN.contains(&name.as_str()) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Builtin codec: {name}"
+        )));
+    }
+    codecs::register(
+        name,
+        std::sync::Arc::new(codecs::PyCodec::new(decode, encode)),
+    );
+    Ok(())
+}
+
 #[pymodule]
 mod redis_rs {
     use pyo3::prelude::*;
@@ -91,9 +242,33 @@ mod redis_rs {
     #[pymodule_export]
     use super::create_client;
 
+    #[pymodule_export]
+    use super::register_codec;
+
     #[pymodule_export]
     use crate::client_async::Client;
 
+    #[pymodule_export]
+    use crate::hooks::Call;
+
+    #[pymodule_export]
+    use crate::pipeline::Pipeline;
+
+    #[pymodule_export]
+    use crate::pubsub::Subscription;
+
+    #[pymodule_export]
+    use crate::scan::ScanIter;
+
+    #[pymodule_export]
+    use crate::script::Script;
+
+    #[pymodule_export]
+    use crate::stream::StreamEntry;
+
+    #[pymodule_export]
+    use crate::transaction::Transaction;
+
     #[pymodule]
     mod exceptions {
 
@@ -102,6 +277,42 @@ mod redis_rs {
 
         #[pymodule_export]
         use crate::exceptions::PoolError;
+
+        #[pymodule_export]
+        use crate::exceptions::WatchError;
+
+        #[pymodule_export]
+        use crate::exceptions::TimeoutError;
+
+        #[pymodule_export]
+        use crate::exceptions::ConnectionError;
+
+        #[pymodule_export]
+        use crate::exceptions::AuthenticationError;
+
+        #[pymodule_export]
+        use crate::exceptions::ResponseError;
+
+        #[pymodule_export]
+        use crate::exceptions::WrongTypeError;
+
+        #[pymodule_export]
+        use crate::exceptions::NoScriptError;
+
+        #[pymodule_export]
+        use crate::exceptions::BusyLoadingError;
+
+        #[pymodule_export]
+        use crate::exceptions::ClusterDownError;
+
+        #[pymodule_export]
+        use crate::exceptions::MovedError;
+
+        #[pymodule_export]
+        use crate::exceptions::AskError;
+
+        #[pymodule_export]
+        use crate::exceptions::ReadOnlyError;
     }
 
     #[pymodule_init]
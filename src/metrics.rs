@@ -0,0 +1,220 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
+
+/// Upper bounds of latency histogram buckets, seconds
+pub const BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Series {
+    pub count: u64,
+    pub errors: u64,
+    pub sum: f64,
+    // non-cumulative counts per bucket, the last one is +Inf
+    pub buckets: Vec<u64>,
+}
+
+impl Series {
+    /// Cumulative counts for every bucket including +Inf
+    pub fn cumulative(&self) -> Vec<u64> {
+        self.buckets
+            .iter()
+            .scan(0, |acc, n| {
+                *acc += n;
+                Some(*acc)
+            })
+            .collect()
+    }
+}
+
+/// Counters of one series, updated without the write lock
+#[derive(Default)]
+struct Counters {
+    count: AtomicU64,
+    errors: AtomicU64,
+    nanos: AtomicU64,
+    buckets: [AtomicU64; BUCKETS.len() + 1],
+}
+
+impl Counters {
+    fn observe(&self, elapsed: Duration, error: bool) {
+        let seconds = elapsed.as_secs_f64();
+        let nanos = elapsed.as_nanos().try_into().unwrap_or(u64::MAX);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.errors.fetch_add(error as u64, Ordering::Relaxed);
+        self.nanos.fetch_add(nanos, Ordering::Relaxed);
+        let n = BUCKETS.partition_point(|le| *le < seconds);
+        self.buckets[n].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn series(&self) -> Series {
+        Series {
+            count: self.count.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            sum: self.nanos.load(Ordering::Relaxed) as f64 / 1e9,
+            buckets: self
+                .buckets
+                .iter()
+                .map(|n| n.load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+}
+
+/// Command counters and latency histograms by command name and node address
+#[derive(Default)]
+pub struct Metrics {
+    series: RwLock<BTreeMap<String, BTreeMap<String, Counters>>>,
+}
+
+impl Metrics {
+    pub fn observe(&self, command: &str, node: &str, elapsed: Duration, error: bool) {
+        // known series only take the read lock, names are copied once on insert
+        let series = self.series.read().unwrap();
+        if let Some(counters) = series.get(command).and_then(|nodes| nodes.get(node)) {
+            return counters.observe(elapsed, error);
+        }
+        drop(series);
+        let mut series = self.series.write().unwrap();
+        let nodes = match series.get_mut(command) {
+            Some(nodes) => nodes,
+            None => series.entry(command.to_string()).or_default(),
+        };
+        let counters = match nodes.get(node) {
+            Some(counters) => counters,
+            None => nodes.entry(node.to_string()).or_default(),
+        };
+        counters.observe(elapsed, error);
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<(String, String), Series> {
+        let series = self.series.read().unwrap();
+        let mut result = BTreeMap::new();
+        for (command, nodes) in series.iter() {
+            for (node, counters) in nodes.iter() {
+                result.insert((command.clone(), node.clone()), counters.series());
+            }
+        }
+        result
+    }
+
+    /// Prometheus text exposition format
+    pub fn prometheus(&self) -> String {
+        let series = self.snapshot();
+        let mut out = String::new();
+        let labels = |command: &str, node: &str| {
+            format!("command=\"{}\",node=\"{}\"", escape(command), escape(node))
+        };
+        out.push_str("# HELP redis_rs_commands_total Commands executed.\n");
+        out.push_str("# TYPE redis_rs_commands_total counter\n");
+        for ((command, node), s) in series.iter() {
+            let labels = labels(command, node);
+            let _ = writeln!(out, "redis_rs_commands_total{{{labels}}} {}", s.count);
+        }
+        out.push_str("# HELP redis_rs_command_errors_total Commands failed.\n");
+        out.push_str("# TYPE redis_rs_command_errors_total counter\n");
+        for ((command, node), s) in series.iter() {
+            let labels = labels(command, node);
+            let _ = writeln!(
+                out,
+                "redis_rs_command_errors_total{{{labels}}} {}",
+                s.errors
+            );
+        }
+        out.push_str("# HELP redis_rs_command_duration_seconds Command latency.\n");
+        out.push_str("# TYPE redis_rs_command_duration_seconds histogram\n");
+        for ((command, node), s) in series.iter() {
+            let labels = labels(command, node);
+            let bounds = BUCKETS.iter().map(|le| le.to_string());
+            for (le, n) in bounds.chain(["+Inf".to_string()]).zip(s.cumulative()) {
+                let _ = writeln!(
+                    out,
+                    "redis_rs_command_duration_seconds_bucket{{{labels},le=\"{le}\"}} {n}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "redis_rs_command_duration_seconds_sum{{{labels}}} {}",
+                s.sum
+            );
+            let _ = writeln!(
+                out,
+                "redis_rs_command_duration_seconds_count{{{labels}}} {}",
+                s.count
+            );
+        }
+        out
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Metrics;
+
+    #[test]
+    fn observe() {
+        let metrics = Metrics::default();
+        metrics.observe("GET", "localhost:6379", Duration::from_millis(2), false);
+        metrics.observe("GET", "localhost:6379", Duration::from_secs(20), true);
+        let series = metrics.snapshot();
+        let s = &series[&("GET".to_string(), "localhost:6379".to_string())];
+        assert_eq!(s.count, 2);
+        assert_eq!(s.errors, 1);
+        let cumulative = s.cumulative();
+        assert_eq!(cumulative[1], 0);
+        assert_eq!(cumulative[2], 1);
+        assert_eq!(cumulative.last(), Some(&2));
+    }
+
+    #[test]
+    fn observe_threads() {
+        let metrics = Metrics::default();
+        std::thread::scope(|scope| {
+            for node in ["a:6379", "b:6379"] {
+                for _ in 0..4 {
+                    let metrics = &metrics;
+                    scope.spawn(move || {
+                        for _ in 0..100 {
+                            metrics.observe("GET", node, Duration::from_millis(1), false);
+                        }
+                    });
+                }
+            }
+        });
+        let series = metrics.snapshot();
+        for node in ["a:6379", "b:6379"] {
+            let s = &series[&("GET".to_string(), node.to_string())];
+            assert_eq!(s.count, 400);
+            assert!((s.sum - 0.4).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn prometheus() {
+        let metrics = Metrics::default();
+        metrics.observe("GET", "a\"b", Duration::from_millis(1), false);
+        let text = metrics.prometheus();
+        assert!(text.contains("redis_rs_commands_total{command=\"GET\",node=\"a\\\"b\"} 1\n"));
+        assert!(text.contains(
+            "redis_rs_command_duration_seconds_bucket{command=\"GET\",node=\"a\\\"b\",le=\"+Inf\"} 1\n"
+        ));
+        assert!(text.contains("# TYPE redis_rs_command_duration_seconds histogram\n"));
+    }
+}
@@ -9,7 +9,12 @@ use async_trait::async_trait;
 use redis::Cmd;
 use tokio::sync::RwLock;
 
-use crate::{command::Params, error, pool::Pool};
+use crate::{
+    command::Params,
+    error,
+    pool::{Connection, Pool},
+    pubsub,
+};
 
 #[derive(Clone, Default)]
 enum InnerValue {
@@ -227,6 +232,71 @@ fn get_db() -> Arc<RwLock<HashMap<i64, DataBase>>> {
         .clone()
 }
 
+type Channel = tokio::sync::broadcast::Sender<(Vec<u8>, Vec<u8>)>;
+
+fn get_channel() -> Channel {
+    use std::sync::OnceLock;
+    static CHANNEL: OnceLock<Channel> = OnceLock::new();
+    CHANNEL
+        .get_or_init(|| tokio::sync::broadcast::channel(1024).0)
+        .clone()
+}
+
+type Scripts = Arc<RwLock<HashMap<Vec<u8>, Vec<u8>>>>;
+
+fn get_scripts() -> Scripts {
+    use std::sync::OnceLock;
+    static SCRIPTS: OnceLock<Scripts> = OnceLock::new();
+    SCRIPTS.get_or_init(Default::default).clone()
+}
+
+// library name -> (code, function names)
+type Functions = Arc<RwLock<BTreeMap<String, (Vec<u8>, Vec<String>)>>>;
+
+fn get_functions() -> Functions {
+    use std::sync::OnceLock;
+    static FUNCTIONS: OnceLock<Functions> = OnceLock::new();
+    FUNCTIONS.get_or_init(Default::default).clone()
+}
+
+fn response_error(msg: &'static str) -> error::RedisError {
+    error::RedisError::RedisError(redis::RedisError::from((
+        redis::ErrorKind::Server(redis::ServerErrorKind::ResponseError),
+        msg,
+    )))
+}
+
+fn parse_library(code: &[u8]) -> Option<(String, Vec<String>)> {
+    let code = String::from_utf8_lossy(code);
+    let (_, name) = code.lines().next()?.split_once("name=")?;
+    let functions = code
+        .split("register_function")
+        .skip(1)
+        .filter_map(|f| f.split(['\'', '"']).nth(1))
+        .map(String::from)
+        .collect();
+    Some((name.trim().to_string(), functions))
+}
+
+fn eval<'a>(script: &[u8], mut args: impl Iterator<Item = &'a [u8]>) -> redis::Value {
+    let mut result = redis::Value::Nil;
+    if script == b"return ARGV[1]" {
+        if let Some(b"0") = args.next() {
+            if let Some(arg) = args.next() {
+                result = redis::Value::BulkString(arg.to_vec());
+            }
+        }
+    }
+    result
+}
+
+fn is_match(pattern: &[u8], channel: &[u8]) -> bool {
+    match pattern.strip_suffix(b"*") {
+        Some(prefix) => channel.starts_with(prefix),
+        None => pattern == channel,
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct MockRedis {
     pub db: i64,
@@ -245,6 +315,56 @@ impl MockRedis {
                 .clone(),
         })
     }
+
+    async fn snapshot(&self, key: &[u8]) -> Option<redis::Value> {
+        let values = self.values.read().await;
+        values.get(key).map(|v| v.value.clone().into())
+    }
+}
+
+pub struct MockConnection {
+    mock: MockRedis,
+    watched: Vec<(Vec<u8>, Option<redis::Value>)>,
+}
+
+#[async_trait]
+impl Connection for MockConnection {
+    async fn execute(
+        &mut self,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        let args: Vec<Vec<u8>> = cmd
+            .args_iter()
+            .filter_map(|arg| match arg {
+                redis::Arg::Simple(s) => Some(s.to_vec()),
+                _ => None,
+            })
+            .collect();
+        match args.split_first() {
+            Some((name, keys)) if name == b"WATCH" => {
+                for key in keys {
+                    let value = self.mock.snapshot(key).await;
+                    self.watched.push((key.clone(), value));
+                }
+                Ok(redis::Value::Okay)
+            }
+            Some((name, _)) if name == b"UNWATCH" => {
+                self.watched.clear();
+                Ok(redis::Value::Okay)
+            }
+            _ => self.mock.execute(cmd, params).await,
+        }
+    }
+
+    async fn exec(&mut self, batch: Vec<(Cmd, Params)>) -> Result<redis::Value, error::RedisError> {
+        for (key, value) in std::mem::take(&mut self.watched) {
+            if self.mock.snapshot(&key).await != value {
+                return Ok(redis::Value::Nil);
+            }
+        }
+        Ok(redis::Value::Array(self.mock.execute_batch(batch).await?))
+    }
 }
 
 #[async_trait]
@@ -493,10 +613,10 @@ impl Pool for MockRedis {
             Some(b"BLPOP") => {
                 let mut result = redis::Value::Nil;
                 let mut keys: Vec<_> = cmd_iter.collect();
-                let _timeout = keys
+                let timeout = keys
                     .pop()
-                    .and_then(|v| String::from_utf8_lossy(v).parse::<usize>().ok())
-                    .unwrap_or(0);
+                    .and_then(|v| String::from_utf8_lossy(v).parse::<f64>().ok())
+                    .unwrap_or(0.0);
                 for key in keys {
                     let mut values = self.values.write().await;
                     let value = values.entry(key.into()).or_insert_with(Value::empty_array);
@@ -509,6 +629,9 @@ impl Pool for MockRedis {
                         break;
                     }
                 }
+                if result == redis::Value::Nil && timeout > 0.0 {
+                    tokio::time::sleep(Duration::from_secs_f64(timeout)).await;
+                }
                 result
             }
             Some(b"LLEN") => {
@@ -723,7 +846,7 @@ impl Pool for MockRedis {
                                 if let InnerValue::Array(ref a) = value.value {
                                     let m = result
                                         .entry(stream)
-                                        .or_insert_with(HashMap::<String, redis::Value>::new);
+                                        .or_insert_with(BTreeMap::<String, redis::Value>::new);
                                     for x in a {
                                         if let redis::Value::Map(msg) = x {
                                             if let Some((
@@ -1078,14 +1201,187 @@ impl Pool for MockRedis {
                 redis::Value::Boolean(true)
             }
             Some(b"EVAL") => {
-                let mut result = redis::Value::Nil;
-                if let Some(b"return ARGV[1]") = cmd_iter.next() {
-                    if let Some(b"0") = cmd_iter.next() {
-                        if let Some(arg) = cmd_iter.next() {
-                            result = redis::Value::BulkString(arg.to_vec());
+                let script = cmd_iter.next().unwrap_or_default();
+                eval(script, cmd_iter)
+            }
+            Some(b"EVALSHA") => {
+                let sha = cmd_iter.next().unwrap_or_default();
+                let scripts = get_scripts();
+                let scripts = scripts.read().await;
+                let Some(script) = scripts.get(sha) else {
+                    return Err(error::RedisError::RedisError(redis::RedisError::from((
+                        redis::ErrorKind::Server(redis::ServerErrorKind::NoScript),
+                        "No matching script",
+                    ))));
+                };
+                eval(script, cmd_iter)
+            }
+            Some(b"SCRIPT") => {
+                let scripts = get_scripts();
+                match cmd_iter.next() {
+                    Some(b"LOAD") => {
+                        let script = cmd_iter.next().unwrap_or_default();
+                        let sha = redis::Script::new(&String::from_utf8_lossy(script))
+                            .get_hash()
+                            .to_string();
+                        let mut scripts = scripts.write().await;
+                        scripts.insert(sha.as_bytes().to_vec(), script.to_vec());
+                        redis::Value::BulkString(sha.into_bytes())
+                    }
+                    Some(b"EXISTS") => {
+                        let scripts = scripts.read().await;
+                        redis::Value::Array(
+                            cmd_iter
+                                .map(|sha| redis::Value::Int(scripts.contains_key(sha) as i64))
+                                .collect(),
+                        )
+                    }
+                    Some(b"FLUSH") => {
+                        scripts.write().await.clear();
+                        redis::Value::Okay
+                    }
+                    _ => redis::Value::Nil,
+                }
+            }
+            Some(b"FUNCTION") => {
+                let functions = get_functions();
+                match cmd_iter.next() {
+                    Some(b"LOAD") => {
+                        let mut code = cmd_iter.next().unwrap_or_default();
+                        let replace = code == b"REPLACE";
+                        if replace {
+                            code = cmd_iter.next().unwrap_or_default();
+                        }
+                        let Some((name, names)) = parse_library(code) else {
+                            return Err(response_error("Missing library metadata"));
+                        };
+                        let mut functions = functions.write().await;
+                        if !replace && functions.contains_key(&name) {
+                            return Err(response_error("Library already exists"));
+                        }
+                        functions.insert(name.clone(), (code.to_vec(), names));
+                        redis::Value::BulkString(name.into_bytes())
+                    }
+                    Some(b"DELETE") => {
+                        let name = String::from_utf8_lossy(cmd_iter.next().unwrap_or_default());
+                        match functions.write().await.remove(name.as_ref()) {
+                            Some(_) => redis::Value::Okay,
+                            None => return Err(response_error("Library not found")),
+                        }
+                    }
+                    Some(b"FLUSH") => {
+                        functions.write().await.clear();
+                        redis::Value::Okay
+                    }
+                    Some(b"LIST") => {
+                        let functions = functions.read().await;
+                        let bulk = |s: &str| redis::Value::BulkString(s.as_bytes().to_vec());
+                        redis::Value::Array(
+                            functions
+                                .iter()
+                                .map(|(name, (_, names))| {
+                                    redis::Value::Array(vec![
+                                        bulk("library_name"),
+                                        bulk(name),
+                                        bulk("engine"),
+                                        bulk("LUA"),
+                                        bulk("functions"),
+                                        redis::Value::Array(
+                                            names
+                                                .iter()
+                                                .map(|f| {
+                                                    redis::Value::Array(vec![
+                                                        bulk("name"),
+                                                        bulk(f),
+                                                        bulk("description"),
+                                                        redis::Value::Nil,
+                                                        bulk("flags"),
+                                                        redis::Value::Array(vec![]),
+                                                    ])
+                                                })
+                                                .collect(),
+                                        ),
+                                    ])
+                                })
+                                .collect(),
+                        )
+                    }
+                    _ => redis::Value::Nil,
+                }
+            }
+            Some(b"FCALL" | b"FCALL_RO") => {
+                let name = String::from_utf8_lossy(cmd_iter.next().unwrap_or_default());
+                let functions = get_functions();
+                let functions = functions.read().await;
+                if !functions
+                    .values()
+                    .any(|(_, names)| names.contains(&name.to_string()))
+                {
+                    return Err(response_error("Function not found"));
+                }
+                // Mocked functions echo their first argument
+                let numkeys: usize = String::from_utf8_lossy(cmd_iter.next().unwrap_or_default())
+                    .parse()
+                    .unwrap_or_default();
+                match cmd_iter.nth(numkeys) {
+                    Some(arg) => redis::Value::BulkString(arg.to_vec()),
+                    None => redis::Value::Nil,
+                }
+            }
+            Some(b"SCAN") => {
+                let _cursor = cmd_iter.next();
+                let mut pattern: &[u8] = b"*";
+                while let Some(arg) = cmd_iter.next() {
+                    if arg.eq_ignore_ascii_case(b"MATCH") {
+                        pattern = cmd_iter.next().unwrap_or(pattern);
+                    }
+                }
+                let values = self.values.read().await;
+                let keys = values
+                    .keys()
+                    .filter(|k| is_match(pattern, k))
+                    .map(|k| redis::Value::BulkString(k.clone()))
+                    .collect();
+                redis::Value::Array(vec![
+                    redis::Value::BulkString(b"0".to_vec()),
+                    redis::Value::Array(keys),
+                ])
+            }
+            Some(b"HSCAN" | b"SSCAN" | b"ZSCAN") => {
+                let mut result = vec![];
+                if let Some(key) = cmd_iter.next() {
+                    let values = self.values.read().await;
+                    match values.get(key).map(|v| &v.value) {
+                        Some(InnerValue::Map(m)) => {
+                            for (k, v) in m.iter() {
+                                result.push(redis::Value::BulkString(k.clone()));
+                                result.push(match v {
+                                    redis::Value::Double(f) => {
+                                        redis::Value::BulkString(f.to_string().into_bytes())
+                                    }
+                                    v => v.clone(),
+                                });
+                            }
                         }
+                        Some(InnerValue::Set(s)) => {
+                            result.extend(s.iter().map(|m| redis::Value::BulkString(m.clone())));
+                        }
+                        _ => {}
                     }
                 }
+                redis::Value::Array(vec![
+                    redis::Value::BulkString(b"0".to_vec()),
+                    redis::Value::Array(result),
+                ])
+            }
+            Some(b"PUBLISH") => {
+                let mut result = redis::Value::Int(0);
+                if let (Some(channel), Some(message)) = (cmd_iter.next(), cmd_iter.next()) {
+                    let n = get_channel()
+                        .send((channel.to_vec(), message.to_vec()))
+                        .unwrap_or_default();
+                    result = redis::Value::Int(n as i64);
+                }
                 result
             }
             _ => redis::Value::Nil,
@@ -1094,7 +1390,78 @@ impl Pool for MockRedis {
         Ok(result)
     }
 
-    fn status(&self) -> HashMap<&str, redis::Value> {
+    async fn execute_batch(
+        &self,
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError> {
+        let mut result = Vec::with_capacity(batch.len());
+        for (cmd, params) in batch.into_iter() {
+            result.push(self.execute(cmd, params).await?);
+        }
+        Ok(result)
+    }
+
+    async fn get_connection(
+        &self,
+        _params: Params,
+    ) -> Result<Box<dyn Connection + Send + Sync>, error::RedisError> {
+        Ok(Box::new(MockConnection {
+            mock: self.clone(),
+            watched: vec![],
+        }))
+    }
+
+    async fn masters(&self) -> Result<Vec<String>, error::RedisError> {
+        Ok(vec![format!("mock/{}", self.db)])
+    }
+
+    async fn node_addr(&self, _cmd: &Cmd, _params: &Params) -> Option<String> {
+        Some(format!("mock/{}", self.db))
+    }
+
+    async fn execute_on(
+        &self,
+        _addr: &str,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        self.execute(cmd, params).await
+    }
+
+    async fn subscribe(
+        &self,
+        channels: Vec<Vec<u8>>,
+        patterns: Vec<Vec<u8>>,
+    ) -> Result<pubsub::Receiver, error::RedisError> {
+        let mut messages = get_channel().subscribe();
+        let (tx, rx) = tokio::sync::mpsc::channel(1024);
+        tokio::spawn(async move {
+            loop {
+                let msg = tokio::select! {
+                    _ = tx.closed() => return,
+                    msg = messages.recv() => msg,
+                };
+                let (channel, payload) = match msg {
+                    Ok(msg) => msg,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(_) => return,
+                };
+                let value = if channels.contains(&channel) {
+                    pubsub::message(channel, payload, None)
+                } else if let Some(p) = patterns.iter().find(|p| is_match(p, &channel)) {
+                    pubsub::message(channel, payload, Some(p.clone()))
+                } else {
+                    continue;
+                };
+                if tx.send(value).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(rx)
+    }
+
+    fn status(&self) -> HashMap<&'static str, redis::Value> {
         let mut result = HashMap::new();
         result.insert("closed", redis::Value::Boolean(false));
         result.insert("impl", redis::Value::SimpleString("mock".into()));
@@ -1105,7 +1472,7 @@ impl Pool for MockRedis {
 
 #[cfg(test)]
 mod tests {
-    use crate::{command::Params, pool::Pool};
+    use crate::{command::Params, pool::Pool, pubsub};
 
     use super::MockRedis;
 
@@ -1129,6 +1496,65 @@ mod tests {
         assert_eq!(result, redis::Value::BulkString(b"1".to_vec()));
     }
 
+    #[tokio::test]
+    async fn execute_batch() {
+        let key = "batch_key";
+        let params = Params::default();
+        let m = MockRedis::new(0).await.unwrap();
+        let batch = vec![
+            (redis::cmd("SET").arg(key).arg(1).to_owned(), params.clone()),
+            (redis::cmd("GET").arg(key).to_owned(), params.clone()),
+            (redis::cmd("DEL").arg(key).to_owned(), params),
+        ];
+        let result = m.execute_batch(batch).await.unwrap();
+        assert_eq!(
+            result,
+            vec![
+                redis::Value::Nil,
+                redis::Value::BulkString(b"1".to_vec()),
+                redis::Value::Int(1),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_exec_abort() {
+        let key = "watch_key";
+        let params = Params::default();
+        let m = MockRedis::new(0).await.unwrap();
+        let mut conn = m.get_connection(params.clone()).await.unwrap();
+        let cmd = redis::cmd("WATCH").arg(key).to_owned();
+        conn.execute(cmd, params.clone()).await.unwrap();
+        let cmd = redis::cmd("SET").arg(key).arg(1).to_owned();
+        m.execute(cmd.clone(), params.clone()).await.unwrap();
+        let result = conn.exec(vec![(cmd, params)]).await.unwrap();
+        assert_eq!(result, redis::Value::Nil);
+    }
+
+    #[tokio::test]
+    async fn publish_subscribe() {
+        let params = Params::default();
+        let m = MockRedis::new(0).await.unwrap();
+        let mut rx = m
+            .subscribe(vec![b"chan".to_vec()], vec![b"pat*".to_vec()])
+            .await
+            .unwrap();
+        for channel in ["other", "chan", "pattern"] {
+            let cmd = redis::cmd("PUBLISH").arg(channel).arg("hi").to_owned();
+            m.execute(cmd, params.clone()).await.unwrap();
+        }
+        let result = rx.recv().await.unwrap();
+        assert_eq!(
+            result,
+            pubsub::message(b"chan".to_vec(), b"hi".to_vec(), None)
+        );
+        let result = rx.recv().await.unwrap();
+        assert_eq!(
+            result,
+            pubsub::message(b"pattern".to_vec(), b"hi".to_vec(), Some(b"pat*".to_vec()))
+        );
+    }
+
     #[tokio::test]
     async fn hset_hget_hgetall() {
         let key = "hkey";
@@ -1,68 +1,293 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
 use async_trait::async_trait;
 use redis::{
-    aio::{ConnectionLike, ConnectionManager, ConnectionManagerConfig},
-    Client, Cmd, ConnectionInfo,
+    aio::{ConnectionLike, ConnectionManager, ConnectionManagerConfig, MultiplexedConnection},
+    Client, Cmd, ConnectionInfo, ErrorKind, RedisError, ServerErrorKind,
 };
 
-use crate::{command::Params, config::Config, error, pool::Pool};
+use crate::{
+    command::{command_name, Params},
+    config::Config,
+    error,
+    health::Health,
+    pool::{Connection, Pool},
+    pubsub,
+    retry::Retry,
+};
 
 type PoolManager = bb8_redis::RedisConnectionManager;
 
-#[derive(Clone)]
-pub struct Node {
-    pub info: ConnectionInfo,
+#[derive(Debug)]
+struct ReadOnly;
+
+impl bb8::CustomizeConnection<MultiplexedConnection, RedisError> for ReadOnly {
+    fn on_acquire<'a>(
+        &'a self,
+        conn: &'a mut MultiplexedConnection,
+    ) -> Pin<Box<dyn Future<Output = Result<(), RedisError>> + Send + 'a>> {
+        Box::pin(async move { redis::cmd("READONLY").query_async(conn).await })
+    }
+}
+
+struct Connections {
     single: ConnectionManager,
     pool: bb8::Pool<PoolManager>,
-    pub id: Option<String>,
 }
 
-impl Node {
-    pub async fn new(info: ConnectionInfo, config: Config) -> Result<Self, error::RedisError> {
+impl Connections {
+    async fn connect(
+        info: &ConnectionInfo,
+        config: &Config,
+        readonly: bool,
+    ) -> Result<Self, error::RedisError> {
         let client = Client::open(info.clone())?;
         let mut cfg = ConnectionManagerConfig::new();
         if let Some(max_delay) = config.max_delay {
             cfg = cfg.set_max_delay(Duration::from_millis(max_delay));
         }
-        let single = ConnectionManager::new_with_config(client, cfg).await?;
+        if config.connect_timeout.is_some() {
+            cfg = cfg.set_connection_timeout(config.connect_timeout);
+        }
+        let mut single = ConnectionManager::new_with_config(client, cfg).await?;
 
-        let pool = bb8::Pool::builder()
+        let mut builder = bb8::Pool::builder()
             .max_size(config.max_size)
             .min_idle(0)
-            .idle_timeout(Some(Duration::new(60, 0)))
-            .build(PoolManager::new(info.clone())?)
-            .await?;
+            .idle_timeout(Some(Duration::new(60, 0)));
+        if let Some(connect_timeout) = config.connect_timeout {
+            builder = builder.connection_timeout(connect_timeout);
+        }
+        if readonly {
+            redis::cmd("READONLY").exec_async(&mut single).await?;
+            builder = builder.connection_customizer(Box::new(ReadOnly));
+        }
+        let pool = builder.build(PoolManager::new(info.clone())?).await?;
+        Ok(Self { single, pool })
+    }
+}
+
+#[derive(Clone)]
+pub struct Node {
+    pub info: ConnectionInfo,
+    conns: Arc<RwLock<Connections>>,
+    pub id: Option<String>,
+    max_delay: Option<u64>,
+    readonly: bool,
+    retry: Retry,
+    health: Arc<Health>,
+}
+
+impl Node {
+    pub async fn new(info: ConnectionInfo, config: Config) -> Result<Self, error::RedisError> {
+        Self::build(info, config, false).await
+    }
 
-        Ok(Self {
-            single,
-            pool,
+    pub async fn replica(info: ConnectionInfo, config: Config) -> Result<Self, error::RedisError> {
+        Self::build(info, config, true).await
+    }
+
+    async fn build(
+        info: ConnectionInfo,
+        config: Config,
+        readonly: bool,
+    ) -> Result<Self, error::RedisError> {
+        let conns = Connections::connect(&info, &config, readonly).await?;
+        let failures = config.health_check.as_ref().map_or(1, |h| h.failures);
+        let node = Self {
+            conns: Arc::new(RwLock::new(conns)),
             info,
             id: None,
-        })
+            max_delay: config.max_delay,
+            readonly,
+            retry: Retry::new(config.retry.clone()),
+            health: Arc::new(Health::new(failures)),
+        };
+        node.spawn_health_check(config);
+        Ok(node)
+    }
+
+    fn single(&self) -> ConnectionManager {
+        self.conns.read().unwrap().single.clone()
+    }
+
+    fn pool(&self) -> bb8::Pool<PoolManager> {
+        self.conns.read().unwrap().pool.clone()
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.health.is_healthy()
+    }
+
+    pub fn health(&self) -> redis::Value {
+        self.health.status()
+    }
+
+    /// PING the node in background and reconnect it once it is unhealthy.
+    /// The task ends when the last clone of the node is dropped, a zero interval disables it
+    fn spawn_health_check(&self, config: Config) {
+        let interval = config.health_check.as_ref().map(|h| h.interval);
+        let Some(interval) = interval.filter(|i| !i.is_zero()) else {
+            return;
+        };
+        let conns = Arc::downgrade(&self.conns);
+        let health = self.health.clone();
+        let info = self.info.clone();
+        let readonly = self.readonly;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(conns) = conns.upgrade() else {
+                    return;
+                };
+                let mut single = conns.read().unwrap().single.clone();
+                let cmd = redis::cmd("PING");
+                let ping = cmd.exec_async(&mut single);
+                let unhealthy = match tokio::time::timeout(interval, ping).await {
+                    Ok(Ok(())) => {
+                        health.success();
+                        continue;
+                    }
+                    Ok(Err(e)) => health.failure(e.to_string()),
+                    Err(_) => health.failure("PING timed out".to_string()),
+                };
+                if unhealthy {
+                    if let Ok(new) = Connections::connect(&info, &config, readonly).await {
+                        *conns.write().unwrap() = new;
+                    }
+                }
+            }
+        });
+    }
+
+    async fn execute_once(
+        &self,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        // server errors are replies, raise them so redirects and retries see them
+        let value = if params.block {
+            let mut c = self.pool().get_owned().await?;
+            c.req_packed_command(&cmd).await?.extract_error()?
+        } else {
+            let mut c = self.single();
+            match c.req_packed_command(&cmd).await?.extract_error() {
+                Err(e)
+                    if self.readonly && e.kind() == ErrorKind::Server(ServerErrorKind::Moved) =>
+                {
+                    redis::cmd("READONLY").exec_async(&mut c).await?;
+                    c.req_packed_command(&cmd).await?.extract_error()?
+                }
+                r => r?,
+            }
+        };
+        Ok(value)
+    }
+}
+
+pub struct NodeConnection(bb8::PooledConnection<'static, PoolManager>);
+
+#[async_trait]
+impl Connection for NodeConnection {
+    async fn execute(
+        &mut self,
+        cmd: Cmd,
+        _params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        Ok(self.0.req_packed_command(&cmd).await?)
+    }
+
+    async fn exec(&mut self, batch: Vec<(Cmd, Params)>) -> Result<redis::Value, error::RedisError> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (cmd, _) in batch.into_iter() {
+            pipe.add_command(cmd);
+        }
+        let mut values = self.0.req_packed_commands(&pipe, pipe.len() + 1, 1).await?;
+        Ok(values.pop().unwrap_or(redis::Value::Nil))
     }
 }
 
 #[async_trait]
 impl Pool for Node {
     async fn execute(&self, cmd: Cmd, params: Params) -> Result<redis::Value, error::RedisError> {
-        let value = if params.block {
-            let mut c = self.pool.get().await?;
-            c.req_packed_command(&cmd).await?
+        self.retry
+            .execute(&cmd, || self.execute_once(cmd.clone(), params.clone()))
+            .await
+            .map_err(|e| {
+                e.with_context(|c| {
+                    c.command = Some(command_name(&cmd));
+                    c.node = Some(self.info.addr().to_string());
+                })
+            })
+    }
+
+    async fn execute_batch(
+        &self,
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError> {
+        let count = batch.len();
+        let block = batch.iter().any(|(_, params)| params.block);
+        let mut pipe = redis::pipe();
+        for (cmd, _) in batch.into_iter() {
+            pipe.add_command(cmd);
+        }
+        let values = if block {
+            let mut c = self.pool().get_owned().await?;
+            c.req_packed_commands(&pipe, 0, count).await?
         } else {
-            let mut c = self.single.clone();
-            c.req_packed_command(&cmd).await?
+            let mut c = self.single();
+            c.req_packed_commands(&pipe, 0, count).await?
         };
-        Ok(value)
+        Ok(values)
+    }
+
+    async fn get_connection(
+        &self,
+        _params: Params,
+    ) -> Result<Box<dyn Connection + Send + Sync>, error::RedisError> {
+        let c = self.pool().get_owned().await?;
+        Ok(Box::new(NodeConnection(c)))
     }
 
-    fn status(&self) -> HashMap<&str, redis::Value> {
+    async fn masters(&self) -> Result<Vec<String>, error::RedisError> {
+        Ok(vec![self.info.addr().to_string()])
+    }
+
+    async fn node_addr(&self, _cmd: &Cmd, _params: &Params) -> Option<String> {
+        Some(self.info.addr().to_string())
+    }
+
+    async fn execute_on(
+        &self,
+        _addr: &str,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        self.execute(cmd, params).await
+    }
+
+    async fn subscribe(
+        &self,
+        channels: Vec<Vec<u8>>,
+        patterns: Vec<Vec<u8>>,
+    ) -> Result<pubsub::Receiver, error::RedisError> {
+        pubsub::listen(self.info.clone(), channels, patterns, self.max_delay).await
+    }
+
+    fn status(&self) -> HashMap<&'static str, redis::Value> {
         let mut result = HashMap::new();
         result.insert("closed", redis::Value::Boolean(false));
         result.insert("impl", redis::Value::SimpleString("client_async".into()));
         result.insert("cluster", redis::Value::Boolean(false));
 
-        let state = self.pool.state();
+        let state = self.pool().state();
         result.insert(
             "connections",
             redis::Value::Int(state.connections as i64 + 1),
@@ -71,6 +296,89 @@ impl Pool for Node {
             "idle_connections",
             redis::Value::Int(state.idle_connections as i64),
         );
+        let addr = redis::Value::SimpleString(self.info.addr().to_string());
+        result.insert("health", redis::Value::Map(vec![(addr, self.health())]));
+        self.retry.status(&mut result);
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use redis::IntoConnectionInfo;
+
+    use crate::{
+        command::Params,
+        config::{Config, HealthCheck},
+        fake,
+        pool::Pool,
+    };
+
+    use super::Node;
+
+    #[tokio::test]
+    async fn server_error() {
+        let addr = fake::serve(|cmd| match fake::name(cmd).as_str() {
+            "GET" => fake::error("MOVED 3999 127.0.0.1:6381"),
+            _ => fake::ok(),
+        })
+        .await;
+        let info = format!("redis://{addr}").into_connection_info().unwrap();
+        let config = Config {
+            max_size: 1,
+            ..Default::default()
+        };
+        let node = Node::new(info, config).await.unwrap();
+        let cmd = redis::cmd("GET").arg("a").to_owned();
+        let e = node.execute(cmd.clone(), Params::from(&cmd)).await;
+        let e = e.unwrap_err();
+        let redirect = e.redis_error().and_then(|e| e.redirect_node());
+        assert_eq!(redirect, Some(("127.0.0.1:6381", 3999)));
+        assert_eq!(e.context().unwrap().node, Some(addr));
+    }
+
+    #[tokio::test]
+    async fn health_check() {
+        let failing = Arc::new(AtomicBool::new(true));
+        let selects = Arc::new(AtomicUsize::new(0));
+        let addr = fake::serve({
+            let (failing, selects) = (failing.clone(), selects.clone());
+            move |cmd| match fake::name(cmd).as_str() {
+                "PING" if failing.load(Ordering::Relaxed) => fake::error("ERR down"),
+                "SELECT" => {
+                    selects.fetch_add(1, Ordering::Relaxed);
+                    fake::ok()
+                }
+                _ => fake::ok(),
+            }
+        })
+        .await;
+        // every new connection selects the database, counting reconnects
+        let info = format!("redis://{addr}/1").into_connection_info().unwrap();
+        let config = Config {
+            max_size: 1,
+            health_check: Some(HealthCheck {
+                interval: Duration::from_millis(10),
+                failures: 2,
+            }),
+            ..Default::default()
+        };
+        let node = Node::new(info, config).await.unwrap();
+        assert!(node.is_healthy());
+        let connected = selects.load(Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!node.is_healthy());
+        assert!(selects.load(Ordering::Relaxed) > connected);
+        failing.store(false, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(node.is_healthy());
+    }
+}
@@ -0,0 +1,43 @@
+use pyo3::prelude::*;
+use redis::Cmd;
+
+use crate::{client_result_async::AsyncClientResult, command::Params, types};
+
+#[pyclass]
+pub struct Pipeline {
+    cr: AsyncClientResult,
+    batch: Vec<(Cmd, Params)>,
+}
+
+impl Pipeline {
+    pub(crate) fn new(cr: AsyncClientResult) -> Self {
+        Self { cr, batch: vec![] }
+    }
+}
+
+#[pymethods]
+impl Pipeline {
+    #[pyo3(signature = (cmd, *args, encoding = None))]
+    fn add(
+        mut slf: PyRefMut<'_, Self>,
+        cmd: types::Str,
+        args: Vec<types::Arg>,
+        encoding: Option<String>,
+    ) -> PyRefMut<'_, Self> {
+        let cmd = String::from(cmd).to_ascii_uppercase();
+        let cmd = redis::cmd(cmd.as_str()).arg(args).to_owned();
+        let mut params = slf.cr.params(&cmd);
+        params.codec = encoding.into();
+        slf.batch.push((cmd, params));
+        slf
+    }
+
+    fn __len__(&self) -> usize {
+        self.batch.len()
+    }
+
+    async fn execute(&mut self) -> PyResult<Py<PyAny>> {
+        let batch = std::mem::take(&mut self.batch);
+        self.cr.execute_batch(batch).await
+    }
+}
@@ -1,15 +1,54 @@
 use std::collections::HashMap;
 
-use crate::{command::Params, error};
+use crate::{command::Params, error, pubsub};
 use async_trait::async_trait;
 
 use redis::Cmd;
 
+#[async_trait]
+pub trait Connection {
+    async fn execute(
+        &mut self,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError>;
+
+    async fn exec(&mut self, batch: Vec<(Cmd, Params)>) -> Result<redis::Value, error::RedisError>;
+}
+
 #[async_trait]
 pub trait Pool {
     async fn execute(&self, cmd: Cmd, params: Params) -> Result<redis::Value, error::RedisError>;
 
-    fn status(&self) -> HashMap<&str, redis::Value>;
+    async fn execute_batch(
+        &self,
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError>;
+
+    async fn get_connection(
+        &self,
+        params: Params,
+    ) -> Result<Box<dyn Connection + Send + Sync>, error::RedisError>;
+
+    async fn masters(&self) -> Result<Vec<String>, error::RedisError>;
+
+    /// Address of the node serving the command, None when it is not known in advance
+    async fn node_addr(&self, cmd: &Cmd, params: &Params) -> Option<String>;
+
+    async fn execute_on(
+        &self,
+        addr: &str,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError>;
+
+    async fn subscribe(
+        &self,
+        channels: Vec<Vec<u8>>,
+        patterns: Vec<Vec<u8>>,
+    ) -> Result<pubsub::Receiver, error::RedisError>;
+
+    fn status(&self) -> HashMap<&'static str, redis::Value>;
 }
 
 pub struct ClosedPool;
@@ -20,7 +59,46 @@ impl Pool for ClosedPool {
         Err(error::RedisError::not_initialized())
     }
 
-    fn status(&self) -> HashMap<&str, redis::Value> {
+    async fn execute_batch(
+        &self,
+        _batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError> {
+        Err(error::RedisError::not_initialized())
+    }
+
+    async fn get_connection(
+        &self,
+        _params: Params,
+    ) -> Result<Box<dyn Connection + Send + Sync>, error::RedisError> {
+        Err(error::RedisError::not_initialized())
+    }
+
+    async fn masters(&self) -> Result<Vec<String>, error::RedisError> {
+        Err(error::RedisError::not_initialized())
+    }
+
+    async fn node_addr(&self, _cmd: &Cmd, _params: &Params) -> Option<String> {
+        None
+    }
+
+    async fn execute_on(
+        &self,
+        _addr: &str,
+        _cmd: Cmd,
+        _params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        Err(error::RedisError::not_initialized())
+    }
+
+    async fn subscribe(
+        &self,
+        _channels: Vec<Vec<u8>>,
+        _patterns: Vec<Vec<u8>>,
+    ) -> Result<pubsub::Receiver, error::RedisError> {
+        Err(error::RedisError::not_initialized())
+    }
+
+    fn status(&self) -> HashMap<&'static str, redis::Value> {
         let mut result = HashMap::new();
         result.insert("closed", redis::Value::Boolean(true));
         result
@@ -1,24 +1,38 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
 
 use redis::{Cmd, FromRedisValue};
 
 use crate::{
+    cache::{self, ClientCache},
     client_async::Client,
     client_result_async::AsyncClientResult,
     cluster_async::Cluster,
-    command::Params,
+    command::{block_timeout, command_name, is_readonly, Params},
     config::Config,
     error,
+    hooks::{Call, Hooks},
+    key_spec::KeySpecs,
+    metrics::Metrics,
     mock::MockRedis,
     node::Node,
-    pool::{ClosedPool, Pool},
+    pool::{ClosedPool, Connection, Pool},
+    sentinel::Sentinel,
     shards_async::AsyncShards,
 };
 
+// how often the masters are checked for tracking
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
 impl From<PoolManager> for Client {
     fn from(value: PoolManager) -> Self {
         let client_id = value.config.client_id.clone();
         let ac = AsyncClientResult {
+            key_specs: value.key_specs.clone(),
             cm: Arc::new(tokio::sync::RwLock::new(value)),
         };
         Self { cr: ac, client_id }
@@ -26,37 +40,95 @@ impl From<PoolManager> for Client {
 }
 
 pub struct PoolManager {
-    pub(crate) pool: Box<dyn Pool + Send + Sync>,
+    pub(crate) pool: Arc<dyn Pool + Send + Sync>,
     pub(crate) config: Config,
+    cache: Option<Arc<ClientCache>>,
+    // follows the masters with tracking connections
+    tracking: Option<tokio::task::JoinHandle<()>>,
+    pub(crate) metrics: Arc<Metrics>,
+    pub(crate) hooks: Arc<Hooks>,
+    pub(crate) key_specs: Arc<KeySpecs>,
 }
 
 impl PoolManager {
     pub fn new(config: Config) -> Result<Self, error::RedisError> {
+        let cache = config
+            .client_cache
+            .as_ref()
+            .map(|options| Arc::new(ClientCache::new(options)));
         Ok(Self {
-            pool: Box::new(ClosedPool),
+            pool: Arc::new(ClosedPool),
             config,
+            cache,
+            tracking: None,
+            metrics: Default::default(),
+            hooks: Default::default(),
+            key_specs: Default::default(),
         })
     }
 
+    async fn start_tracking(&mut self) -> Result<(), error::RedisError> {
+        self.stop_tracking();
+        let Some(cache) = self.cache.clone() else {
+            return Ok(());
+        };
+        // The in-memory mock has no other writers to track
+        if !self.config.mock {
+            let mut tracking = cache::Tracking::default();
+            let masters = self.pool.masters().await?;
+            tracking.update(&cache, &self.config, masters).await?;
+            self.tracking = Some(tokio::spawn(watch_masters(
+                Arc::downgrade(&self.pool),
+                cache.clone(),
+                self.config.clone(),
+                tracking,
+            )));
+        }
+        cache.enable();
+        Ok(())
+    }
+
+    fn stop_tracking(&mut self) {
+        if let Some(handle) = self.tracking.take() {
+            handle.abort();
+        }
+        if let Some(ref cache) = self.cache {
+            cache.flush();
+        }
+    }
+
     pub async fn init(&mut self) -> Result<(), error::RedisError> {
         let mut nodes = self.config.initial_nodes.clone();
-        let ms = self.config.max_size;
         self.pool = if self.config.mock {
             let db = nodes.first().map(|a| a.redis_settings().db()).unwrap_or(0);
-            Box::new(MockRedis::new(db).await?)
+            Arc::new(MockRedis::new(db).await?)
+        } else if self.config.service_name.is_some() {
+            Arc::new(Sentinel::new(self.config.clone()).await?)
         } else if self.config.shards || self.config.cluster.is_none() {
-            Box::new(AsyncShards::new(self.config.clone()).await?)
+            Arc::new(AsyncShards::new(self.config.clone()).await?)
         } else {
-            match self.config.cluster.unwrap() {
-                true => Box::new(Cluster::new(nodes, ms).await?),
-                false => Box::new(Node::new(nodes.remove(0), self.config.clone()).await?),
+            match self.config.cluster {
+                Some(true) => Arc::new(Cluster::new(self.config.clone()).await?),
+                _ => Arc::new(Node::new(nodes.remove(0), self.config.clone()).await?),
             }
         };
+        // key specs are loaded once, the mock is served by the bundled table
+        if !self.config.mock && !self.key_specs.is_loaded() {
+            if let Ok(commands) = self
+                .pool
+                .execute(redis::cmd("COMMAND"), Params::default())
+                .await
+            {
+                self.key_specs.load(commands);
+            }
+        }
+        self.start_tracking().await?;
         Ok(())
     }
 
     pub async fn close(&mut self) {
-        self.pool = Box::new(ClosedPool);
+        self.stop_tracking();
+        self.pool = Arc::new(ClosedPool);
     }
 
     pub fn status(&self) -> HashMap<String, redis::Value> {
@@ -77,27 +149,323 @@ impl PoolManager {
             .collect();
         result.insert("initial_nodes", redis::Value::Array(initial_nodes));
         result.insert("max_size", redis::Value::Int(self.config.max_size as i64));
+        if let Some(ref cache) = self.cache {
+            result.insert("client_cache", cache.status());
+        }
         result
             .into_iter()
             .map(|(k, v)| (k.to_string(), v))
             .collect()
     }
 
+    /// Client timeout for the command, extended by the server-side timeout of blocking ones
+    fn timeout(&self, cmd: &Cmd, params: &Params) -> Option<Duration> {
+        let timeout = params.timeout.or(self.config.response_timeout)?;
+        if params.block {
+            block_timeout(cmd).map(|block| timeout + block)
+        } else {
+            Some(timeout)
+        }
+    }
+
+    /// Execute a command through the registered hooks
+    pub async fn query(
+        &self,
+        mut cmd: Cmd,
+        mut params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        let hooks = self.hooks.get();
+        if hooks.is_empty() {
+            return self.query_timed(cmd, params, None).await;
+        }
+        let node = self.pool.node_addr(&cmd, &params).await;
+        let mut call = Call::new(&cmd, &params.keys, node);
+        let start = Instant::now();
+        let mut reply = None;
+        for hook in hooks.iter() {
+            match hook.before(&mut call).await {
+                Ok(None) => continue,
+                Ok(Some(value)) => reply = Some(Ok(value)),
+                Err(e) => reply = Some(Err(e)),
+            }
+            break;
+        }
+        let result = match reply {
+            Some(result) => result,
+            None => {
+                if let Some(rewritten) = call.rewritten() {
+                    let Params { keys, block, .. } =
+                        Params::with_specs(&rewritten, &self.key_specs);
+                    (cmd, params.keys, params.block) = (rewritten, keys, block);
+                    call.keys = params.keys.clone();
+                    call.node = self.pool.node_addr(&cmd, &params).await;
+                }
+                self.query_timed(cmd, params, Some(call.node.clone())).await
+            }
+        };
+        let elapsed = start.elapsed();
+        for hook in hooks.iter() {
+            hook.after(&call, elapsed, &result).await;
+        }
+        result
+    }
+
+    /// Execute a command bounded by the response timeout and record its metrics,
+    /// the node is looked up unless the hooks already did
+    async fn query_timed(
+        &self,
+        cmd: Cmd,
+        params: Params,
+        node: Option<Option<String>>,
+    ) -> Result<redis::Value, error::RedisError> {
+        let node = match node {
+            Some(node) => node,
+            None => self.pool.node_addr(&cmd, &params).await,
+        };
+        let timeout = self.timeout(&cmd, &params);
+        let command = command_name(&cmd);
+        self.observed(command, node, timeout, self.query_cached(cmd, params))
+            .await
+    }
+
+    /// Bound a request by the timeout, record its metrics and add the error context
+    async fn observed(
+        &self,
+        command: String,
+        node: Option<String>,
+        timeout: Option<Duration>,
+        request: impl Future<Output = Result<redis::Value, error::RedisError>>,
+    ) -> Result<redis::Value, error::RedisError> {
+        let start = Instant::now();
+        let result = bounded(timeout, request).await;
+        let failed = matches!(result, Err(_) | Ok(redis::Value::ServerError(_)));
+        let node = node.unwrap_or_default();
+        self.metrics
+            .observe(&command, &node, start.elapsed(), failed);
+        result.map_err(|e| {
+            e.with_context(|c| {
+                c.command.get_or_insert(command);
+                if c.node.is_none() && !node.is_empty() {
+                    c.node = Some(node);
+                }
+            })
+        })
+    }
+
+    /// Command name and node of every command of a batch
+    async fn labels(&self, batch: &[(Cmd, Params)]) -> Vec<(String, String)> {
+        let mut labels = vec![];
+        for (cmd, params) in batch.iter() {
+            let node = self.pool.node_addr(cmd, params).await.unwrap_or_default();
+            labels.push((command_name(cmd), node));
+        }
+        labels
+    }
+
+    /// Record every command of a batch with the time the whole batch took
+    fn observe_batch(
+        &self,
+        labels: &[(String, String)],
+        elapsed: Duration,
+        result: Result<&[redis::Value], ()>,
+    ) {
+        for (n, (command, node)) in labels.iter().enumerate() {
+            let failed = match result {
+                Ok(values) => matches!(values.get(n), Some(redis::Value::ServerError(_))),
+                Err(_) => true,
+            };
+            self.metrics.observe(command, node, elapsed, failed);
+        }
+    }
+
+    /// Longest timeout of the commands, None when a blocking one is unbounded
+    fn batch_timeout(&self, batch: &[(Cmd, Params)]) -> Option<Duration> {
+        let mut timeout = None;
+        for (cmd, params) in batch.iter() {
+            match self.timeout(cmd, params) {
+                Some(t) => timeout = timeout.max(Some(t)),
+                None if params.block => return None,
+                None => {}
+            }
+        }
+        timeout
+    }
+
+    pub async fn execute_batch(
+        &self,
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError> {
+        let labels = self.labels(&batch).await;
+        let timeout = self.batch_timeout(&batch);
+        for (cmd, params) in batch.iter() {
+            self.invalidate_write(cmd, params);
+        }
+        let start = Instant::now();
+        let result = bounded(timeout, self.pool.execute_batch(batch)).await;
+        let values = result.as_deref().map_err(|_| ());
+        self.observe_batch(&labels, start.elapsed(), values);
+        result
+    }
+
+    /// Execute a command on the node at `addr`, or on the node owning its keys
+    pub async fn query_on(
+        &self,
+        addr: Option<String>,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        let node = match addr {
+            Some(ref addr) => Some(addr.clone()),
+            None => self.pool.node_addr(&cmd, &params).await,
+        };
+        let command = command_name(&cmd);
+        let timeout = self.timeout(&cmd, &params);
+        let request = async {
+            match addr {
+                Some(addr) => self.pool.execute_on(&addr, cmd, params).await,
+                None => self.pool.execute(cmd, params).await,
+            }
+        };
+        self.observed(command, node, timeout, request).await
+    }
+
+    /// Execute a command on the dedicated connection of a transaction
+    pub async fn query_connection(
+        &self,
+        conn: &mut (dyn Connection + Send + Sync),
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        let node = self.pool.node_addr(&cmd, &params).await;
+        let command = command_name(&cmd);
+        let timeout = self.timeout(&cmd, &params);
+        self.invalidate_write(&cmd, &params);
+        self.observed(command, node, timeout, conn.execute(cmd, params))
+            .await
+    }
+
+    /// Execute a transaction on its dedicated connection, recording every queued command
+    pub async fn exec_connection(
+        &self,
+        conn: &mut (dyn Connection + Send + Sync),
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<redis::Value, error::RedisError> {
+        let labels = self.labels(&batch).await;
+        let timeout = self.batch_timeout(&batch);
+        for (cmd, params) in batch.iter() {
+            self.invalidate_write(cmd, params);
+        }
+        let start = Instant::now();
+        let result = bounded(timeout, conn.exec(batch)).await;
+        let values = match result {
+            Ok(redis::Value::Array(ref values)) => Ok(values.as_slice()),
+            Ok(_) => Ok(&[][..]),
+            Err(_) => Err(()),
+        };
+        self.observe_batch(&labels, start.elapsed(), values);
+        result
+    }
+
+    /// Drop own writes from the client cache right away instead of waiting for the invalidation
+    fn invalidate_write(&self, cmd: &Cmd, params: &Params) {
+        if let Some(ref cache) = self.cache {
+            if !is_readonly(cmd) {
+                cache.invalidate(&params.keys);
+            }
+        }
+    }
+
+    /// Execute a command, serving cacheable reads from the client cache
+    async fn query_cached(
+        &self,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        let Some(ref cache) = self.cache else {
+            return self.pool.execute(cmd, params).await;
+        };
+        let Some(cache_key) = ClientCache::cache_key(&cmd) else {
+            self.invalidate_write(&cmd, &params);
+            return self.pool.execute(cmd, params).await;
+        };
+        if let Some(value) = cache.get(&cache_key) {
+            return Ok(value);
+        }
+        let epoch = cache.epoch();
+        let value = self.pool.execute(cmd, params).await?;
+        cache.insert(cache_key, value.clone(), epoch);
+        Ok(value)
+    }
+
     pub async fn execute<T: FromRedisValue>(
         &self,
         cmd: Cmd,
         params: Params,
     ) -> Result<T, error::RedisError> {
-        let value = self.pool.execute(cmd, params).await?;
+        let value = self.query(cmd, params).await?;
         let result: T = FromRedisValue::from_redis_value(value)
             .map_err(|e| error::RedisError::RedisError(e.into()))?;
         Ok(result)
     }
 }
 
+/// Fail with `Timeout` when the request takes longer than the timeout
+async fn bounded<T>(
+    timeout: Option<Duration>,
+    request: impl Future<Output = Result<T, error::RedisError>>,
+) -> Result<T, error::RedisError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, request)
+            .await
+            .map_err(|_| error::RedisError::Timeout(timeout))?,
+        None => request.await,
+    }
+}
+
+/// Track masters added or replaced after init, e.g. by resharding or a sentinel failover.
+/// A failed update leaves the cache paused until the next round
+async fn watch_masters(
+    pool: Weak<dyn Pool + Send + Sync>,
+    cache: Arc<ClientCache>,
+    config: Config,
+    mut tracking: cache::Tracking,
+) {
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+        let Some(pool) = pool.upgrade() else {
+            return;
+        };
+        if let Ok(masters) = pool.masters().await {
+            let _ = tracking.update(&cache, &config, masters).await;
+        }
+    }
+}
+
+impl Drop for PoolManager {
+    fn drop(&mut self) {
+        self.stop_tracking();
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::config::Config;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+
+    use crate::{
+        command::Params,
+        config::Config,
+        error, fake,
+        hooks::{Call, Hook},
+        pool::Connection,
+    };
 
     use super::PoolManager;
 
@@ -117,4 +485,216 @@ mod tests {
         pm.close().await;
         assert_eq!(result.len(), 5);
     }
+
+    #[tokio::test]
+    async fn response_timeout() {
+        let mut cfg = Config::mock();
+        cfg.response_timeout = Some(Duration::from_millis(10));
+        let mut pm = PoolManager::new(cfg).unwrap();
+        pm.init().await.unwrap();
+        let cmd = redis::cmd("BLPOP").arg("timeout").arg(0.2).to_owned();
+        let params = Params {
+            block: false,
+            ..Params::from(&cmd)
+        };
+        let result = pm.query(cmd.clone(), params).await;
+        let e = result.unwrap_err();
+        assert!(matches!(e.inner(), error::RedisError::Timeout(_)));
+        assert_eq!(e.context().unwrap().command.as_deref(), Some("BLPOP"));
+        // the server-side timeout is added for blocking commands
+        let result = pm.query(cmd.clone(), Params::from(&cmd)).await;
+        assert_eq!(result.unwrap(), redis::Value::Nil);
+        pm.close().await;
+    }
+
+    struct Slow;
+
+    #[async_trait]
+    impl Connection for Slow {
+        async fn execute(
+            &mut self,
+            _cmd: redis::Cmd,
+            _params: Params,
+        ) -> Result<redis::Value, error::RedisError> {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(redis::Value::Okay)
+        }
+
+        async fn exec(
+            &mut self,
+            _batch: Vec<(redis::Cmd, Params)>,
+        ) -> Result<redis::Value, error::RedisError> {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(redis::Value::Array(vec![]))
+        }
+    }
+
+    #[tokio::test]
+    async fn response_timeout_scan_transaction() {
+        let mut cfg = Config::mock();
+        cfg.response_timeout = Some(Duration::from_millis(10));
+        let mut pm = PoolManager::new(cfg).unwrap();
+        pm.init().await.unwrap();
+        let cmd = redis::cmd("BLPOP").arg("timeout").arg(0.2).to_owned();
+        let params = Params {
+            block: false,
+            ..Params::from(&cmd)
+        };
+        let e = pm.query_on(None, cmd, params).await.unwrap_err();
+        assert!(matches!(e.inner(), error::RedisError::Timeout(_)));
+        let cmd = redis::cmd("GET").arg("a").to_owned();
+        let e = pm
+            .query_connection(&mut Slow, cmd.clone(), Params::from(&cmd))
+            .await
+            .unwrap_err();
+        assert!(matches!(e.inner(), error::RedisError::Timeout(_)));
+        let batch = vec![(cmd.clone(), Params::from(&cmd))];
+        let e = pm.exec_connection(&mut Slow, batch).await.unwrap_err();
+        assert!(matches!(e.inner(), error::RedisError::Timeout(_)));
+        pm.close().await;
+    }
+
+    #[tokio::test]
+    async fn metrics() {
+        let mut pm = PoolManager::new(Config::mock()).unwrap();
+        pm.init().await.unwrap();
+        let cmd = redis::cmd("get").arg("metrics").to_owned();
+        pm.query(cmd.clone(), Params::from(&cmd)).await.unwrap();
+        let series = pm.metrics.snapshot();
+        let s = &series[&("GET".to_string(), "mock/0".to_string())];
+        assert_eq!(s.count, 1);
+        assert_eq!(s.errors, 0);
+        pm.close().await;
+    }
+
+    #[tokio::test]
+    async fn metrics_batch() {
+        let mut pm = PoolManager::new(Config::mock()).unwrap();
+        pm.init().await.unwrap();
+        let set = redis::cmd("SET").arg("metrics_batch").arg(1).to_owned();
+        let get = redis::cmd("GET").arg("metrics_batch").to_owned();
+        let batch = vec![
+            (set.clone(), Params::from(&set)),
+            (get.clone(), Params::from(&get)),
+        ];
+        pm.execute_batch(batch).await.unwrap();
+        let series = pm.metrics.snapshot();
+        assert_eq!(series[&("SET".to_string(), "mock/0".to_string())].count, 1);
+        assert_eq!(series[&("GET".to_string(), "mock/0".to_string())].count, 1);
+        pm.close().await;
+    }
+
+    #[tokio::test]
+    async fn cache_invalidate_writes() {
+        let cfg = Config {
+            client_cache: Some(Default::default()),
+            ..Config::mock()
+        };
+        let mut pm = PoolManager::new(cfg).unwrap();
+        pm.init().await.unwrap();
+        let get = redis::cmd("GET").arg("cache_writes").to_owned();
+        let set = |v: i64| redis::cmd("SET").arg("cache_writes").arg(v).to_owned();
+        assert_eq!(
+            pm.query(get.clone(), Params::from(&get)).await.unwrap(),
+            redis::Value::Nil
+        );
+
+        let cmd = set(1);
+        pm.execute_batch(vec![(cmd.clone(), Params::from(&cmd))])
+            .await
+            .unwrap();
+        assert_eq!(
+            pm.query(get.clone(), Params::from(&get)).await.unwrap(),
+            redis::Value::BulkString(b"1".to_vec())
+        );
+
+        let cmd = set(2);
+        let mut conn = pm.pool.get_connection(Params::from(&cmd)).await.unwrap();
+        pm.exec_connection(conn.as_mut(), vec![(cmd.clone(), Params::from(&cmd))])
+            .await
+            .unwrap();
+        assert_eq!(
+            pm.query(get.clone(), Params::from(&get)).await.unwrap(),
+            redis::Value::BulkString(b"2".to_vec())
+        );
+
+        let cmd = set(3);
+        pm.query_connection(conn.as_mut(), cmd.clone(), Params::from(&cmd))
+            .await
+            .unwrap();
+        assert_eq!(
+            pm.query(get.clone(), Params::from(&get)).await.unwrap(),
+            redis::Value::BulkString(b"3".to_vec())
+        );
+        pm.close().await;
+    }
+
+    struct Rewrite;
+
+    #[async_trait]
+    impl Hook for Rewrite {
+        async fn before(&self, call: &mut Call) -> Result<Option<redis::Value>, error::RedisError> {
+            match call.name.as_str() {
+                "GET" => call.set_args(vec![b"rewritten".to_vec()]),
+                "PING" => return Ok(Some(redis::Value::SimpleString("hooked".into()))),
+                _ => {}
+            }
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn hooks() {
+        let mut pm = PoolManager::new(Config::mock()).unwrap();
+        pm.init().await.unwrap();
+        pm.hooks.add(Arc::new(Rewrite));
+        let cmd = redis::cmd("SET").arg("rewritten").arg("1").to_owned();
+        pm.query(cmd.clone(), Params::from(&cmd)).await.unwrap();
+        let cmd = redis::cmd("GET").arg("original").to_owned();
+        let value = pm.query(cmd.clone(), Params::from(&cmd)).await.unwrap();
+        assert_eq!(value, redis::Value::BulkString(b"1".to_vec()));
+        let cmd = redis::cmd("PING").to_owned();
+        let value = pm.query(cmd.clone(), Params::from(&cmd)).await.unwrap();
+        assert_eq!(value, redis::Value::SimpleString("hooked".into()));
+        pm.close().await;
+    }
+
+    #[tokio::test]
+    async fn tracking_sentinel() {
+        let tracking = Arc::new(AtomicUsize::new(0));
+        let addr = fake::serve({
+            let tracking = tracking.clone();
+            move |cmd| match (fake::name(cmd).as_str(), cmd.get(1).map(|a| a.as_slice())) {
+                ("HELLO", _) => b"%0\r\n".to_vec(),
+                ("CLIENT", Some(b"TRACKING")) => {
+                    tracking.fetch_add(1, Ordering::Relaxed);
+                    fake::ok()
+                }
+                _ => fake::ok(),
+            }
+        })
+        .await;
+        let sentinel = fake::serve({
+            let addr = addr.clone();
+            move |cmd| match fake::name(cmd).as_str() {
+                "SENTINEL" => fake::master(&addr),
+                _ => fake::ok(),
+            }
+        })
+        .await;
+        let mut config = Config {
+            max_size: 1,
+            client_cache: Some(Default::default()),
+            ..Default::default()
+        };
+        config
+            .set_sentinels(vec![format!("redis://{sentinel}")], "mymaster".into())
+            .unwrap();
+        config.set_protocol(3).unwrap();
+        let mut pm = PoolManager::new(config).unwrap();
+        pm.init().await.unwrap();
+        // the master found by the sentinels is tracked without initial nodes
+        assert_eq!(tracking.load(Ordering::Relaxed), 1);
+        pm.close().await;
+    }
 }
@@ -0,0 +1,181 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use pyo3::{exceptions::PyStopAsyncIteration, prelude::*, types::PyDict, IntoPyObjectExt};
+use redis::{aio::PubSub, Client, ConnectionInfo, PushKind, Value};
+use tokio::sync::{mpsc, Mutex, Notify};
+
+use crate::{client_result_async::tokio_rt, error, types};
+
+pub type Receiver = mpsc::Receiver<Value>;
+
+const MIN_DELAY: u64 = 100; // ms
+
+pub fn message(channel: Vec<u8>, payload: Vec<u8>, pattern: Option<Vec<u8>>) -> Value {
+    match pattern {
+        Some(pattern) => Value::Push {
+            kind: PushKind::PMessage,
+            data: vec![
+                Value::BulkString(pattern),
+                Value::BulkString(channel),
+                Value::BulkString(payload),
+            ],
+        },
+        None => Value::Push {
+            kind: PushKind::Message,
+            data: vec![Value::BulkString(channel), Value::BulkString(payload)],
+        },
+    }
+}
+
+async fn connect<F, Fut>(
+    target: &F,
+    channels: &[Vec<u8>],
+    patterns: &[Vec<u8>],
+) -> Result<PubSub, error::RedisError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<ConnectionInfo, error::RedisError>>,
+{
+    let client = Client::open(target().await?)?;
+    let mut pubsub = client.get_async_pubsub().await?;
+    if !channels.is_empty() {
+        pubsub.subscribe(channels).await?;
+    }
+    if !patterns.is_empty() {
+        pubsub.psubscribe(patterns).await?;
+    }
+    Ok(pubsub)
+}
+
+pub async fn listen(
+    info: ConnectionInfo,
+    channels: Vec<Vec<u8>>,
+    patterns: Vec<Vec<u8>>,
+    max_delay: Option<u64>,
+) -> Result<Receiver, error::RedisError> {
+    let target = move || std::future::ready(Ok(info.clone()));
+    listen_with(target, channels, patterns, max_delay).await
+}
+
+/// Like `listen`, asking the target for the server before every reconnect
+pub async fn listen_with<F, Fut>(
+    target: F,
+    channels: Vec<Vec<u8>>,
+    patterns: Vec<Vec<u8>>,
+    max_delay: Option<u64>,
+) -> Result<Receiver, error::RedisError>
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<ConnectionInfo, error::RedisError>> + Send,
+{
+    let mut pubsub = connect(&target, &channels, &patterns).await?;
+    let max_delay = Duration::from_millis(max_delay.unwrap_or(10_000).max(MIN_DELAY));
+    let (tx, rx) = mpsc::channel(1024);
+    tokio::spawn(async move {
+        loop {
+            let mut stream = pubsub.into_on_message();
+            loop {
+                let msg = tokio::select! {
+                    _ = tx.closed() => return,
+                    msg = stream.next() => msg,
+                };
+                let Some(msg) = msg else {
+                    break;
+                };
+                let pattern = msg.from_pattern().then(|| msg.get_pattern().ok()).flatten();
+                let channel = msg.get_channel().unwrap_or_default();
+                let value = message(channel, msg.get_payload_bytes().to_vec(), pattern);
+                if tx.send(value).await.is_err() {
+                    return;
+                }
+            }
+            let mut delay = Duration::from_millis(MIN_DELAY);
+            pubsub = loop {
+                tokio::select! {
+                    _ = tx.closed() => return,
+                    _ = tokio::time::sleep(delay) => {},
+                };
+                match connect(&target, &channels, &patterns).await {
+                    Ok(pubsub) => break pubsub,
+                    Err(_) => delay = (delay * 2).min(max_delay),
+                }
+            };
+        }
+    });
+    Ok(rx)
+}
+
+#[pyclass]
+pub struct Subscription {
+    rx: Arc<Mutex<Receiver>>,
+    closed: Arc<Notify>,
+    codec: types::Codec,
+}
+
+impl Subscription {
+    pub(crate) fn new(rx: Receiver, codec: types::Codec) -> Self {
+        Self {
+            rx: Arc::new(Mutex::new(rx)),
+            closed: Arc::new(Notify::new()),
+            codec,
+        }
+    }
+}
+
+#[pymethods]
+impl Subscription {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__(slf: Bound<'_, Self>) -> PyResult<Bound<'_, PyAny>> {
+        slf.call_method0("_anext")
+    }
+
+    async fn _anext(&self) -> PyResult<Py<PyAny>> {
+        let rx = self.rx.clone();
+        let closed = self.closed.clone();
+        let value = tokio_rt()
+            .spawn(async move {
+                let mut rx = rx.lock().await;
+                tokio::select! {
+                    value = rx.recv() => value,
+                    _ = closed.notified() => {
+                        rx.close();
+                        None
+                    }
+                }
+            })
+            .await
+            .unwrap();
+        let Some(Value::Push { kind, data }) = value else {
+            return Err(PyStopAsyncIteration::new_err(()));
+        };
+        let mut data = data.into_iter().map(|v| match v {
+            Value::BulkString(b) => b,
+            _ => vec![],
+        });
+        let pattern = match kind {
+            PushKind::PMessage => data.next(),
+            _ => None,
+        };
+        let channel = data.next().unwrap_or_default();
+        let payload = Value::BulkString(data.next().unwrap_or_default());
+        Python::attach(|py| {
+            let result = PyDict::new(py);
+            result.set_item("type", kind.to_string())?;
+            result.set_item(
+                "pattern",
+                pattern.map(|p| String::from_utf8_lossy(&p).to_string()),
+            )?;
+            result.set_item("channel", String::from_utf8_lossy(&channel))?;
+            result.set_item("data", types::to_object(py, payload, self.codec.clone())?)?;
+            result.into_py_any(py)
+        })
+    }
+
+    fn close(&self) {
+        self.closed.notify_one();
+    }
+}
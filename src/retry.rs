@@ -0,0 +1,218 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use redis::{Cmd, ErrorKind, ServerErrorKind};
+
+use crate::{
+    config::{RetryOn, RetryPolicy},
+    error,
+};
+
+/// Retry policy applied to `Pool::execute` with its counters
+#[derive(Clone, Default)]
+pub struct Retry {
+    policy: RetryPolicy,
+    retries: Arc<AtomicU64>,
+    exhausted: Arc<AtomicU64>,
+}
+
+impl Retry {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    fn is_retryable(&self, e: &error::RedisError) -> bool {
+        let Some(e) = e.redis_error() else {
+            return false;
+        };
+        self.policy.retry_on.iter().any(|on| match on {
+            RetryOn::Io => e.is_io_error() || e.is_connection_dropped(),
+            RetryOn::TryAgain => e.kind() == ErrorKind::Server(ServerErrorKind::TryAgain),
+            RetryOn::ClusterDown => e.kind() == ErrorKind::Server(ServerErrorKind::ClusterDown),
+            RetryOn::Loading => e.kind() == ErrorKind::Server(ServerErrorKind::BusyLoading),
+        })
+    }
+
+    /// Exponential backoff with equal jitter
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .policy
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.policy.max_backoff);
+        let half = delay / 2;
+        let jitter = RandomState::new().build_hasher().finish() % (half.as_micros() as u64 + 1);
+        half + Duration::from_micros(jitter)
+    }
+
+    pub async fn execute<F, Fut>(&self, cmd: &Cmd, f: F) -> Result<redis::Value, error::RedisError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<redis::Value, error::RedisError>>,
+    {
+        let safe = self.policy.can_replay(cmd);
+        let mut attempt = 1;
+        loop {
+            match f().await {
+                Err(e) if safe && self.is_retryable(&e) => {
+                    if attempt >= self.policy.max_attempts {
+                        if attempt > 1 {
+                            self.exhausted.fetch_add(1, Ordering::Relaxed);
+                        }
+                        return Err(e);
+                    }
+                    tokio::time::sleep(self.backoff(attempt as u32 - 1)).await;
+                    self.retries.fetch_add(1, Ordering::Relaxed);
+                    attempt += 1;
+                }
+                r => return r,
+            }
+        }
+    }
+
+    pub fn status(&self, result: &mut HashMap<&'static str, redis::Value>) {
+        let retries = self.retries.load(Ordering::Relaxed) as i64;
+        let exhausted = self.exhausted.load(Ordering::Relaxed) as i64;
+        result.insert("retries", redis::Value::Int(retries));
+        result.insert("retries_exhausted", redis::Value::Int(exhausted));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use crate::{config::RetryPolicy, error};
+
+    use super::Retry;
+
+    fn try_again() -> error::RedisError {
+        error::RedisError::RedisError(redis::RedisError::from((
+            redis::ErrorKind::Server(redis::ServerErrorKind::TryAgain),
+            "try again",
+        )))
+    }
+
+    fn io_error() -> error::RedisError {
+        let e = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        error::RedisError::RedisError(redis::RedisError::from(e))
+    }
+
+    fn retry() -> Retry {
+        Retry::new(RetryPolicy {
+            backoff: Duration::from_millis(1),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn backoff() {
+        let retry = retry();
+        assert!(retry.backoff(0) <= Duration::from_millis(1));
+        assert!(retry.backoff(30) <= retry.policy.max_backoff);
+        assert!(retry.backoff(30) >= retry.policy.max_backoff / 2);
+    }
+
+    #[tokio::test]
+    async fn retry_readonly() {
+        let retry = retry();
+        let calls = AtomicUsize::new(0);
+        let cmd = redis::cmd("GET").arg("a").to_owned();
+        let result = retry
+            .execute(&cmd, || async {
+                match calls.fetch_add(1, Ordering::Relaxed) {
+                    0 => Err(try_again()),
+                    _ => Ok(redis::Value::Okay),
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), redis::Value::Okay);
+        assert_eq!(retry.retries.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn no_retry_writes() {
+        let retry = retry();
+        let calls = AtomicUsize::new(0);
+        let cmd = redis::cmd("INCR").arg("a").to_owned();
+        let result = retry
+            .execute(&cmd, || async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Err(try_again())
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn exhausted() {
+        let retry = retry();
+        let cmd = redis::cmd("GET").arg("a").to_owned();
+        let result = retry.execute(&cmd, || async { Err(try_again()) }).await;
+        assert!(result.is_err());
+        assert_eq!(retry.retries.load(Ordering::Relaxed), 2);
+        assert_eq!(retry.exhausted.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_io() {
+        let retry = retry();
+        let calls = AtomicUsize::new(0);
+        let cmd = redis::cmd("SET").arg("a").arg(1).to_owned();
+        let result = retry
+            .execute(&cmd, || async {
+                match calls.fetch_add(1, Ordering::Relaxed) {
+                    0 => Err(io_error()),
+                    _ => Ok(redis::Value::Okay),
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), redis::Value::Okay);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn no_retry_conditional_set() {
+        let retry = retry();
+        let calls = AtomicUsize::new(0);
+        let cmd = redis::cmd("SET").arg("a").arg(1).arg("NX").to_owned();
+        let result = retry
+            .execute(&cmd, || async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Err(io_error())
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        let retry = Retry::new(RetryPolicy::disabled());
+        let calls = AtomicUsize::new(0);
+        let cmd = redis::cmd("GET").arg("a").to_owned();
+        let result = retry
+            .execute(&cmd, || async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Err(io_error())
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
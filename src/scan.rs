@@ -0,0 +1,156 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use pyo3::{exceptions::PyStopAsyncIteration, prelude::*, IntoPyObjectExt};
+use redis::Value;
+use tokio::sync::Mutex;
+
+use crate::{client_result_async::AsyncClientResult, command::Params, error, types};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Keys,
+    Members,
+    Fields,
+    Scores,
+}
+
+impl Kind {
+    fn command(&self) -> &'static str {
+        match self {
+            Kind::Keys => "SCAN",
+            Kind::Members => "SSCAN",
+            Kind::Fields => "HSCAN",
+            Kind::Scores => "ZSCAN",
+        }
+    }
+
+    fn is_pairs(&self) -> bool {
+        matches!(self, Kind::Fields | Kind::Scores)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    started: bool,
+    // node address (None for routing by key) and its cursor
+    cursors: VecDeque<(Option<String>, Vec<u8>)>,
+    items: VecDeque<Value>,
+}
+
+#[pyclass]
+pub struct ScanIter {
+    cr: AsyncClientResult,
+    kind: Kind,
+    key: Option<Vec<u8>>,
+    options: Vec<Vec<u8>>,
+    codec: types::Codec,
+    state: Arc<Mutex<State>>,
+}
+
+impl ScanIter {
+    pub(crate) fn new(
+        cr: AsyncClientResult,
+        kind: Kind,
+        key: Option<Vec<u8>>,
+        pattern: Option<types::Str>,
+        count: Option<usize>,
+        type_: Option<types::Str>,
+        codec: types::Codec,
+    ) -> Self {
+        let mut options = vec![];
+        if let Some(pattern) = pattern {
+            options.push(b"MATCH".to_vec());
+            options.push(Vec::from(&pattern));
+        }
+        if let Some(count) = count {
+            options.push(b"COUNT".to_vec());
+            options.push(count.to_string().into_bytes());
+        }
+        if let Some(type_) = type_ {
+            options.push(b"TYPE".to_vec());
+            options.push(Vec::from(&type_));
+        }
+        Self {
+            cr,
+            kind,
+            key,
+            options,
+            codec,
+            state: Default::default(),
+        }
+    }
+
+    async fn next_items(&self, state: &mut State) -> PyResult<bool> {
+        if !state.started {
+            state.started = true;
+            if self.key.is_some() {
+                state.cursors.push_back((None, b"0".to_vec()));
+            } else {
+                for addr in self.cr.masters().await? {
+                    state.cursors.push_back((Some(addr), b"0".to_vec()));
+                }
+            }
+        }
+        let Some((addr, cursor)) = state.cursors.pop_front() else {
+            return Ok(false);
+        };
+        let mut cmd = redis::cmd(self.kind.command());
+        let params = match self.key {
+            Some(ref key) => {
+                cmd.arg(key);
+                Params::from(key.as_slice())
+            }
+            None => Params::default(),
+        };
+        cmd.arg(cursor).arg(&self.options);
+        let value = self.cr.execute_on(addr.clone(), cmd, params).await?;
+        let (cursor, items): (Vec<u8>, Vec<Value>) = redis::FromRedisValue::from_redis_value(value)
+            .map_err(|e| error::RedisError::RedisError(e.into()))?;
+        if cursor != b"0" {
+            state.cursors.push_front((addr, cursor));
+        }
+        state.items.extend(items);
+        Ok(true)
+    }
+}
+
+#[pymethods]
+impl ScanIter {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__(slf: Bound<'_, Self>) -> PyResult<Bound<'_, PyAny>> {
+        slf.call_method0("_anext")
+    }
+
+    async fn _anext(&self) -> PyResult<Py<PyAny>> {
+        let mut state = self.state.lock().await;
+        let width = if self.kind.is_pairs() { 2 } else { 1 };
+        while state.items.len() < width {
+            if !self.next_items(&mut state).await? {
+                return Err(PyStopAsyncIteration::new_err(()));
+            }
+        }
+        let first = state.items.pop_front().unwrap_or_default();
+        let second = if self.kind.is_pairs() {
+            state.items.pop_front()
+        } else {
+            None
+        };
+        Python::attach(|py| match (self.kind, second) {
+            (Kind::Fields, Some(value)) => {
+                let field: String = redis::FromRedisValue::from_redis_value(first)
+                    .map_err(|e| error::RedisError::RedisError(e.into()))?;
+                let value = types::to_object(py, value, self.codec.clone())?;
+                (field, value).into_py_any(py)
+            }
+            (Kind::Scores, Some(score)) => {
+                let member = types::to_object(py, first, self.codec.clone())?;
+                let score = types::to_object(py, score, types::Codec::Float)?;
+                (member, score).into_py_any(py)
+            }
+            _ => types::to_object(py, first, self.codec.clone()),
+        })
+    }
+}
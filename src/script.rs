@@ -0,0 +1,43 @@
+use pyo3::prelude::*;
+
+use crate::{client_result_async::AsyncClientResult, command::Params, types};
+
+#[pyclass]
+pub struct Script {
+    cr: AsyncClientResult,
+    source: String,
+    #[pyo3(get)]
+    sha: String,
+}
+
+impl Script {
+    pub(crate) fn new(cr: AsyncClientResult, source: String) -> Self {
+        let sha = redis::Script::new(&source).get_hash().to_string();
+        Self { cr, source, sha }
+    }
+}
+
+#[pymethods]
+impl Script {
+    #[pyo3(signature = (keys = None, args = None, *, encoding = None))]
+    async fn __call__(
+        &self,
+        keys: Option<Vec<types::Str>>,
+        args: Option<Vec<types::Arg>>,
+        encoding: Option<String>,
+    ) -> PyResult<Py<PyAny>> {
+        let keys = keys.unwrap_or_default();
+        let mut params = Params::from(&keys);
+        params.codec = encoding.into();
+        params.block = true;
+        let cmd = redis::cmd("EVALSHA")
+            .arg(self.sha.as_str())
+            .arg(keys.len())
+            .arg(keys)
+            .arg(args.unwrap_or_default())
+            .to_owned();
+        self.cr
+            .execute_script(self.source.clone(), cmd, params)
+            .await
+    }
+}
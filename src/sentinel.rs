@@ -0,0 +1,395 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use async_trait::async_trait;
+use redis::{Cmd, ConnectionInfo, ServerErrorKind};
+
+use crate::{
+    command::Params,
+    config::Config,
+    error,
+    node::Node,
+    pool::{Connection, Pool},
+    pubsub,
+};
+
+fn is_failover(e: &error::RedisError) -> bool {
+    match e.inner() {
+        error::RedisError::RedisError(e) | error::RedisError::PoolError(e) => {
+            e.is_io_error()
+                || e.is_connection_refusal()
+                || e.is_connection_dropped()
+                || e.kind() == redis::ErrorKind::Server(ServerErrorKind::ReadOnly)
+        }
+        _ => false,
+    }
+}
+
+/// The master refused the command, so it was not applied
+fn is_rejected(e: &error::RedisError) -> bool {
+    e.redis_error().is_some_and(|e| {
+        e.is_connection_refusal() || e.kind() == redis::ErrorKind::Server(ServerErrorKind::ReadOnly)
+    })
+}
+
+async fn master_addr(info: &ConnectionInfo, service_name: &str) -> Option<String> {
+    let client = redis::Client::open(info.clone()).ok()?;
+    let mut conn = client.get_multiplexed_async_connection().await.ok()?;
+    let result: redis::RedisResult<Option<(String, u16)>> = redis::cmd("SENTINEL")
+        .arg("GET-MASTER-ADDR-BY-NAME")
+        .arg(service_name)
+        .query_async(&mut conn)
+        .await;
+    let (host, port) = result.ok()??;
+    Some(format!("{host}:{port}"))
+}
+
+/// Address of the master, from the first sentinel answering within the connect timeout
+async fn resolve(config: &Config) -> Result<String, error::RedisError> {
+    let service_name = config.service_name.as_deref().unwrap_or_default();
+    for info in config.sentinels.iter() {
+        let addr = match config.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, master_addr(info, service_name))
+                .await
+                .ok()
+                .flatten(),
+            None => master_addr(info, service_name).await,
+        };
+        if let Some(addr) = addr {
+            return Ok(addr);
+        }
+    }
+    Err(error::RedisError::PoolError(redis::RedisError::from((
+        redis::ErrorKind::Io,
+        "Master not found by sentinels",
+        service_name.to_string(),
+    ))))
+}
+
+pub struct Sentinel {
+    config: Config,
+    master: Arc<RwLock<(String, Node)>>,
+}
+
+impl Sentinel {
+    pub async fn new(config: Config) -> Result<Self, error::RedisError> {
+        let addr = resolve(&config).await?;
+        let node = Self::create_node(&config, &addr).await?;
+        Ok(Self {
+            config,
+            master: Arc::new(RwLock::new((addr, node))),
+        })
+    }
+
+    async fn create_node(config: &Config, addr: &str) -> Result<Node, error::RedisError> {
+        Node::new(config.node_info(addr)?, config.clone()).await
+    }
+
+    fn node(&self) -> Node {
+        self.master.read().unwrap().1.clone()
+    }
+
+    async fn failover(&self) -> Result<Node, error::RedisError> {
+        let addr = resolve(&self.config).await?;
+        if self.master.read().unwrap().0 == addr {
+            return Ok(self.node());
+        }
+        let node = Self::create_node(&self.config, &addr).await?;
+        *self.master.write().unwrap() = (addr, node.clone());
+        Ok(node)
+    }
+
+    /// Send again to the new master unless the write could have been applied by the old one
+    fn can_resend(&self, cmd: &Cmd, e: &error::RedisError) -> bool {
+        is_rejected(e) || self.config.retry.can_replay(cmd)
+    }
+}
+
+#[async_trait]
+impl Pool for Sentinel {
+    async fn execute(&self, cmd: Cmd, params: Params) -> Result<redis::Value, error::RedisError> {
+        match self.node().execute(cmd.clone(), params.clone()).await {
+            Err(e) if is_failover(&e) => {
+                let node = self.failover().await?;
+                match self.can_resend(&cmd, &e) {
+                    true => node.execute(cmd, params).await,
+                    false => Err(e),
+                }
+            }
+            r => r,
+        }
+    }
+
+    async fn execute_batch(
+        &self,
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError> {
+        match self.node().execute_batch(batch.clone()).await {
+            Err(e) if is_failover(&e) => {
+                let node = self.failover().await?;
+                match batch.iter().all(|(cmd, _)| self.can_resend(cmd, &e)) {
+                    true => node.execute_batch(batch).await,
+                    false => Err(e),
+                }
+            }
+            r => r,
+        }
+    }
+
+    async fn get_connection(
+        &self,
+        params: Params,
+    ) -> Result<Box<dyn Connection + Send + Sync>, error::RedisError> {
+        match self.node().get_connection(params.clone()).await {
+            Err(e) if is_failover(&e) => self.failover().await?.get_connection(params).await,
+            r => r,
+        }
+    }
+
+    async fn masters(&self) -> Result<Vec<String>, error::RedisError> {
+        Ok(vec![self.master.read().unwrap().0.clone()])
+    }
+
+    async fn node_addr(&self, _cmd: &Cmd, _params: &Params) -> Option<String> {
+        Some(self.master.read().unwrap().0.clone())
+    }
+
+    async fn execute_on(
+        &self,
+        _addr: &str,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        self.execute(cmd, params).await
+    }
+
+    async fn subscribe(
+        &self,
+        channels: Vec<Vec<u8>>,
+        patterns: Vec<Vec<u8>>,
+    ) -> Result<pubsub::Receiver, error::RedisError> {
+        // resolved on every reconnect, to follow the master after a failover
+        let config = Arc::new(self.config.clone());
+        let target = move || {
+            let config = config.clone();
+            async move { Ok(config.node_info(&resolve(&config).await?)?) }
+        };
+        pubsub::listen_with(target, channels, patterns, self.config.max_delay).await
+    }
+
+    fn status(&self) -> HashMap<&'static str, redis::Value> {
+        let (addr, node) = self.master.read().unwrap().clone();
+        let mut result = node.status();
+        result.insert("impl", redis::Value::SimpleString("sentinel".into()));
+        result.insert("master", redis::Value::SimpleString(addr));
+        let service_name = self.config.service_name.clone().unwrap_or_default();
+        result.insert("service_name", redis::Value::SimpleString(service_name));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use redis::Value;
+
+    use crate::{command::Params, config::Config, error, fake, pool::Pool, pubsub};
+
+    use super::{is_failover, Sentinel};
+
+    #[test]
+    fn failover_errors() {
+        let e = redis::RedisError::from((redis::ErrorKind::Io, "io"));
+        assert!(is_failover(&error::RedisError::RedisError(e)));
+        let e = redis::RedisError::from((
+            redis::ErrorKind::Server(redis::ServerErrorKind::ReadOnly),
+            "readonly",
+        ));
+        assert!(is_failover(&error::RedisError::RedisError(e)));
+        let e = redis::RedisError::from((
+            redis::ErrorKind::Server(redis::ServerErrorKind::ResponseError),
+            "err",
+        ));
+        assert!(!is_failover(&error::RedisError::RedisError(e)));
+        assert!(!is_failover(&error::RedisError::NoSlot));
+    }
+
+    fn master(status: &std::collections::HashMap<&str, Value>) -> Value {
+        status["master"].clone()
+    }
+
+    /// A master demoted to replica, the sentinel names the new master from the second query
+    async fn sentinel() -> (Sentinel, String, String) {
+        let old = fake::serve(|cmd| match fake::name(cmd).as_str() {
+            "SET" | "INCR" => fake::error("READONLY You can't write against a read only replica."),
+            _ => fake::ok(),
+        })
+        .await;
+        let new = fake::serve(|cmd| match fake::name(cmd).as_str() {
+            "INCR" => b":1\r\n".to_vec(),
+            _ => fake::ok(),
+        })
+        .await;
+        let queries = Arc::new(AtomicUsize::new(0));
+        let (o, n) = (old.clone(), new.clone());
+        let sentinel = fake::serve(move |cmd| match fake::name(cmd).as_str() {
+            "SENTINEL" if queries.fetch_add(1, Ordering::Relaxed) == 0 => fake::master(&o),
+            "SENTINEL" => fake::master(&n),
+            _ => fake::ok(),
+        })
+        .await;
+        let mut config = Config {
+            max_size: 1,
+            ..Default::default()
+        };
+        config
+            .set_sentinels(vec![format!("redis://{sentinel}")], "mymaster".into())
+            .unwrap();
+        (Sentinel::new(config).await.unwrap(), old, new)
+    }
+
+    #[tokio::test]
+    async fn failover() {
+        let (sentinel, old, new) = sentinel().await;
+        assert_eq!(master(&sentinel.status()), Value::SimpleString(old));
+        let cmd = redis::cmd("SET").arg("a").arg(1).arg("NX").to_owned();
+        // READONLY means the old master did not apply the write
+        let value = sentinel.execute(cmd.clone(), Params::from(&cmd)).await;
+        assert_eq!(value.unwrap(), Value::Okay);
+        assert_eq!(master(&sentinel.status()), Value::SimpleString(new));
+    }
+
+    #[tokio::test]
+    async fn no_resend_after_io_error() {
+        let (sentinel, _, new) = sentinel().await;
+        let cmd = redis::cmd("INCR").arg("a").to_owned();
+        let e = redis::RedisError::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        let e = error::RedisError::RedisError(e);
+        assert!(!sentinel.can_resend(&cmd, &e));
+        let cmd = redis::cmd("GET").arg("a").to_owned();
+        assert!(sentinel.can_resend(&cmd, &e));
+        // the new master is still picked up for the next commands
+        sentinel.failover().await.unwrap();
+        assert_eq!(master(&sentinel.status()), Value::SimpleString(new));
+    }
+
+    #[tokio::test]
+    async fn resolve_timeout() {
+        // accepts connections and never replies
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut sockets = vec![];
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+        let addr = fake::serve(|_| fake::ok()).await;
+        let a = addr.clone();
+        let sentinel = fake::serve(move |cmd| match fake::name(cmd).as_str() {
+            "SENTINEL" => fake::master(&a),
+            _ => fake::ok(),
+        })
+        .await;
+        let mut config = Config {
+            max_size: 1,
+            connect_timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let sentinels = [silent, sentinel].map(|addr| format!("redis://{addr}"));
+        config
+            .set_sentinels(sentinels.to_vec(), "mymaster".into())
+            .unwrap();
+        // well within the 1s the redis crate waits by default
+        let sentinel = tokio::time::timeout(Duration::from_millis(800), Sentinel::new(config));
+        let sentinel = sentinel.await.unwrap().unwrap();
+        assert_eq!(master(&sentinel.status()), Value::SimpleString(addr));
+    }
+
+    #[tokio::test]
+    async fn subscribe_failover() {
+        let ack = b"*3\r\n$9\r\nsubscribe\r\n$2\r\nch\r\n:1\r\n".to_vec();
+        let reply = ack.clone();
+        // the old master drops the subscriber with an unparsable reply
+        let old = fake::serve(move |cmd| match fake::name(cmd).as_str() {
+            "SUBSCRIBE" => [reply.as_slice(), b"?\r\n"].concat(),
+            _ => fake::ok(),
+        })
+        .await;
+        let new = fake::serve(move |cmd| match fake::name(cmd).as_str() {
+            "SUBSCRIBE" => [
+                ack.as_slice(),
+                b"*3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$2\r\nhi\r\n",
+            ]
+            .concat(),
+            _ => fake::ok(),
+        })
+        .await;
+        // the client and the first subscription see the old master
+        let queries = Arc::new(AtomicUsize::new(0));
+        let (o, n) = (old.clone(), new.clone());
+        let sentinel = fake::serve(move |cmd| match fake::name(cmd).as_str() {
+            "SENTINEL" if queries.fetch_add(1, Ordering::Relaxed) < 2 => fake::master(&o),
+            "SENTINEL" => fake::master(&n),
+            _ => fake::ok(),
+        })
+        .await;
+        let mut config = Config {
+            max_size: 1,
+            ..Default::default()
+        };
+        config
+            .set_sentinels(vec![format!("redis://{sentinel}")], "mymaster".into())
+            .unwrap();
+        let sentinel = Sentinel::new(config).await.unwrap();
+        let mut rx = sentinel
+            .subscribe(vec![b"ch".to_vec()], vec![])
+            .await
+            .unwrap();
+        let msg = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await;
+        let msg = msg.unwrap().unwrap();
+        assert_eq!(msg, pubsub::message(b"ch".to_vec(), b"hi".to_vec(), None));
+    }
+
+    #[tokio::test]
+    async fn protocol() {
+        // HELLO sent to every server, with the protocol version
+        let hello = Arc::new(Mutex::new(vec![]));
+        let reply = |hello: Arc<Mutex<Vec<String>>>, name: &'static str| {
+            move |cmd: &[Vec<u8>]| match fake::name(cmd).as_str() {
+                "HELLO" => {
+                    let version = String::from_utf8_lossy(&cmd[1]);
+                    hello.lock().unwrap().push(format!("{name} {version}"));
+                    b"%0\r\n".to_vec()
+                }
+                "SENTINEL" => fake::master(&String::from_utf8_lossy(&cmd[2])),
+                _ => fake::ok(),
+            }
+        };
+        let addr = fake::serve(reply(hello.clone(), "master")).await;
+        let sentinel = fake::serve(reply(hello.clone(), "sentinel")).await;
+        let mut config = Config {
+            max_size: 1,
+            ..Default::default()
+        };
+        // the master address is passed as service name to the fake sentinel
+        config
+            .set_sentinels(vec![format!("redis://{sentinel}")], addr.clone())
+            .unwrap();
+        config.set_protocol(3).unwrap();
+        let sentinel = Sentinel::new(config).await.unwrap();
+        assert_eq!(master(&sentinel.status()), Value::SimpleString(addr));
+        let hello = hello.lock().unwrap();
+        assert!(hello.contains(&"sentinel 3".to_string()));
+        assert!(hello.contains(&"master 3".to_string()));
+    }
+}
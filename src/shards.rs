@@ -26,7 +26,7 @@ fn sub_key(key: &[u8]) -> &[u8] {
         .unwrap_or(key)
 }
 
-fn slot(key: &[u8]) -> u16 {
+pub(crate) fn slot(key: &[u8]) -> u16 {
     let key = sub_key(key);
     crc16::State::<crc16::XMODEM>::calculate(key) % SLOT_SIZE
 }
@@ -156,6 +156,24 @@ impl Slots {
     pub(crate) fn get_nodes(&self) -> Vec<ShardNode> {
         self.id_map.values().cloned().collect()
     }
+
+    pub(crate) fn get_masters(&self) -> Vec<String> {
+        let mut result: Vec<String> = self.slots.values().map(|s| s.master.clone()).collect();
+        result.sort();
+        result.dedup();
+        result
+    }
+
+    pub(crate) fn get_replicas(&self) -> Vec<String> {
+        let mut result: Vec<String> = self
+            .slots
+            .values()
+            .flat_map(|s| s.slaves.iter().cloned())
+            .collect();
+        result.sort();
+        result.dedup();
+        result
+    }
 }
 
 impl FromRedisValue for Slots {
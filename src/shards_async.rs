@@ -1,19 +1,248 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use async_trait::async_trait;
-use redis::{Cmd, ConnectionInfo, IntoConnectionInfo, RedisError, RedisResult, Value};
+use redis::{Cmd, ConnectionInfo, ErrorKind, RedisError, RedisResult, ServerErrorKind, Value};
 use tokio::sync::RwLock;
 
-use crate::{command::Params, config::Config, error, pool::Pool, shards::Slots};
+use crate::{
+    command::{fanout, is_readonly, multi_key, Aggregate, Fanout, Merge, Params},
+    config::{Config, ReadFrom, RetryPolicy},
+    error,
+    pool::{Connection, Pool},
+    pubsub,
+    retry::Retry,
+    shards::{slot, Slots},
+};
 
 type Node = crate::node::Node;
 
+const MAX_REDIRECTS: usize = 5;
+
+fn cross_slot() -> error::RedisError {
+    error::RedisError::CommandError("CROSSSLOT Keys in request don't hash to the same slot".into())
+}
+
+/// Connection pinned to one node, in cluster mode its commands must share one slot
+struct ShardConnection {
+    conn: Box<dyn Connection + Send + Sync>,
+    addr: String,
+    slots: Option<Arc<RwLock<Slots>>>,
+    slot: Option<u16>,
+}
+
+impl ShardConnection {
+    /// Pin the slot of the first keyed command and refuse keys of other slots
+    async fn check(&mut self, params: &Params) -> Result<(), error::RedisError> {
+        let Some(ref slots) = self.slots else {
+            return Ok(());
+        };
+        for key in params.keys.iter() {
+            match self.slot {
+                Some(s) if slot(key) != s => return Err(cross_slot()),
+                Some(_) => {}
+                None => {
+                    let shard = slots.read().await.get_route(params).shard;
+                    if shard.is_some_and(|shard| shard.master != self.addr) {
+                        return Err(error::RedisError::CommandError(format!(
+                            "Keys are not served by the connected node {}",
+                            self.addr
+                        )));
+                    }
+                    self.slot = Some(slot(key));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Connection for ShardConnection {
+    async fn execute(
+        &mut self,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        self.check(&params).await?;
+        self.conn.execute(cmd, params).await
+    }
+
+    async fn exec(&mut self, batch: Vec<(Cmd, Params)>) -> Result<redis::Value, error::RedisError> {
+        for (_, params) in batch.iter() {
+            self.check(params).await?;
+        }
+        self.conn.exec(batch).await
+    }
+}
+
+/// Merge the replies of a fan-out command, given in node address order
+fn aggregate(
+    aggregate: Aggregate,
+    values: Vec<(String, Value)>,
+) -> Result<Value, error::RedisError> {
+    let mut checked = vec![];
+    for (addr, value) in values.into_iter() {
+        match value.extract_error() {
+            Ok(value) => checked.push((addr, value)),
+            Err(e) => {
+                return Err(error::RedisError::from(e).with_context(|c| c.node = Some(addr)));
+            }
+        }
+    }
+    let values = checked.into_iter();
+    let value = match aggregate {
+        Aggregate::Sum => Value::Int(
+            values
+                .map(|(_, v)| match v {
+                    Value::Int(i) => i,
+                    _ => 0,
+                })
+                .sum(),
+        ),
+        Aggregate::Concat => Value::Array(
+            values
+                .flat_map(|(_, v)| match v {
+                    Value::Array(a) | Value::Set(a) => a,
+                    v => vec![v],
+                })
+                .collect(),
+        ),
+        // every node must give the same reply, OK or the same SHA / library name
+        Aggregate::AllOk => {
+            let mut values = values;
+            let first = values.next().map_or(Value::Okay, |(_, v)| v);
+            if let Some((addr, _)) = values.find(|(_, v)| *v != first) {
+                return Err(error::RedisError::CommandError(format!(
+                    "Unexpected reply from {addr}, other nodes replied {first:?}"
+                )));
+            }
+            first
+        }
+        Aggregate::ByNode => Value::Map(
+            values
+                .map(|(addr, v)| (Value::SimpleString(addr), v))
+                .collect(),
+        ),
+        // SCRIPT EXISTS, a script exists only when every node has it
+        Aggregate::And => {
+            let mut exists: Option<Vec<bool>> = None;
+            for (_, v) in values {
+                let flags = match v {
+                    Value::Array(a) => a
+                        .iter()
+                        .map(|v| matches!(v, Value::Int(1) | Value::Boolean(true)))
+                        .collect(),
+                    _ => vec![],
+                };
+                exists = Some(match exists {
+                    Some(exists) => exists.into_iter().zip(flags).map(|(a, b)| a && b).collect(),
+                    None => flags,
+                });
+            }
+            let exists = exists.unwrap_or_default().into_iter();
+            Value::Array(exists.map(|e| Value::Int(e as i64)).collect())
+        }
+    };
+    Ok(value)
+}
+
+/// Key positions in the original command with the part covering them
+type Parts = Vec<(Vec<usize>, Cmd)>;
+
+/// Parts of a multi-key command with one slot each.
+/// Returns `None` when all keys belong to one slot
+fn split_by_slot(cmd: &Cmd) -> Option<(Merge, Parts)> {
+    let mut args = cmd.args_iter().filter_map(|arg| match arg {
+        redis::Arg::Simple(s) => Some(s),
+        _ => None,
+    });
+    let name = args.next()?.to_ascii_uppercase();
+    let (merge, step) = multi_key(&name)?;
+    let args: Vec<&[u8]> = args.collect();
+    let mut by_slot: BTreeMap<u16, Vec<usize>> = BTreeMap::new();
+    for (n, key) in args.iter().step_by(step).enumerate() {
+        by_slot.entry(slot(key)).or_default().push(n);
+    }
+    if by_slot.len() < 2 {
+        return None;
+    }
+    let parts = by_slot
+        .into_values()
+        .map(|positions| {
+            let mut part = redis::cmd(&String::from_utf8_lossy(&name));
+            for n in positions.iter() {
+                for arg in &args[n * step..(n + 1) * step] {
+                    part.arg(*arg);
+                }
+            }
+            (positions, part)
+        })
+        .collect();
+    Some((merge, parts))
+}
+
+/// Merge the replies of the parts of a split command, failing when any part failed
+fn merge_parts(merge: Merge, parts: Vec<(Vec<usize>, Value)>) -> Result<Value, error::RedisError> {
+    let unexpected =
+        |value: &Value| error::RedisError::CommandError(format!("Unexpected reply: {value:?}"));
+    let mut checked = vec![];
+    for (positions, value) in parts.into_iter() {
+        checked.push((positions, value.extract_error()?));
+    }
+    let value = match merge {
+        Merge::Values => {
+            let count = checked.iter().map(|(p, _)| p.len()).sum();
+            let mut result = vec![Value::Nil; count];
+            for (positions, value) in checked.into_iter() {
+                let Value::Array(values) = value else {
+                    return Err(unexpected(&value));
+                };
+                for (n, value) in positions.into_iter().zip(values) {
+                    result[n] = value;
+                }
+            }
+            Value::Array(result)
+        }
+        Merge::Sum => {
+            let mut sum = 0;
+            for (_, value) in checked.iter() {
+                match value {
+                    Value::Int(i) => sum += i,
+                    value => return Err(unexpected(value)),
+                }
+            }
+            Value::Int(sum)
+        }
+        Merge::AllOk => match checked.iter().find(|(_, v)| *v != Value::Okay) {
+            Some((_, value)) => return Err(unexpected(value)),
+            None => Value::Okay,
+        },
+    };
+    Ok(value)
+}
+
+#[derive(Default)]
+struct SplitGroup {
+    addr: String,
+    positions: Vec<Vec<usize>>,
+    batch: Vec<(Cmd, Params)>,
+}
+
 #[derive(Default, Clone)]
 pub struct AsyncShards {
     slots: Arc<RwLock<Slots>>,
     nodes: Arc<RwLock<HashMap<String, Node>>>,
+    replicas: Arc<RwLock<HashMap<String, Node>>>,
+    counter: Arc<AtomicUsize>,
     config: Config,
     is_cluster: bool,
+    retry: Retry,
 }
 
 impl AsyncShards {
@@ -21,6 +250,7 @@ impl AsyncShards {
         let is_cluster = config.cluster;
         let init_nodes = config.initial_nodes.clone();
         let mut result = Self {
+            retry: Retry::new(config.retry.clone()),
             config,
             ..Default::default()
         };
@@ -40,8 +270,16 @@ impl AsyncShards {
         Ok(result)
     }
 
+    // Retries are applied once around the routed command, not per node
+    fn node_config(&self) -> Config {
+        Config {
+            retry: RetryPolicy::disabled(),
+            ..self.config.clone()
+        }
+    }
+
     async fn create_node(&self, info: ConnectionInfo) -> Result<Node, error::RedisError> {
-        Node::new(info, self.config.clone()).await
+        Node::new(info, self.node_config()).await
     }
 
     async fn init_cluster(&self) -> Result<bool, error::RedisError> {
@@ -100,11 +338,14 @@ impl AsyncShards {
         Err(error::RedisError::not_initialized())
     }
 
-    async fn send_command(&self, cmd: &redis::Cmd) -> Result<redis::Value, error::RedisError> {
+    async fn send_command(
+        &self,
+        cmd: &redis::Cmd,
+        params: &Params,
+    ) -> Result<redis::Value, error::RedisError> {
         let slots = self.slots.read().await;
         let nodes = self.nodes.read().await;
-        let params = Params::from(cmd);
-        let addr = if let Some(shard) = slots.get_route(&params).shard {
+        let addr = if let Some(shard) = slots.get_route(params).shard {
             shard.master
         } else {
             nodes
@@ -117,50 +358,477 @@ impl AsyncShards {
             .get(addr.as_str())
             .ok_or(error::RedisError::NotFoundNode)?;
         let node = node.clone();
-        let r = node.execute(cmd.clone(), params).await?;
+        let r = node.execute(cmd.clone(), params.clone()).await?;
         Ok(r)
     }
 
     async fn send_command_with_add_node(
         &self,
         cmd: &redis::Cmd,
+        params: &Params,
     ) -> Result<redis::Value, error::RedisError> {
         let slots = self.slots.read().await;
-        let params = Params::from(cmd);
-        let route = slots.get_route(&params);
+        let route = slots.get_route(params);
         let shard = route.shard.ok_or(error::RedisError::NoSlot)?;
         let addr = shard.master.as_str();
         let mut nodes = self.nodes.write().await;
         let node = if let Some(node) = nodes.get(addr) {
             node
         } else {
-            let ninfo = ("redis://".to_string() + shard.master.as_str()).into_connection_info()?;
-            let node = self.create_node(ninfo).await?;
+            let node = self.create_node(self.node_info(addr)?).await?;
             nodes.insert(addr.to_string(), node);
             nodes.get(addr).ok_or(error::RedisError::NotFoundNode)?
         };
         let node = node.clone();
-        let r = node.execute(cmd.clone(), params).await?;
+        let r = node.execute(cmd.clone(), params.clone()).await?;
         Ok(r)
     }
+
+    fn node_info(&self, addr: &str) -> RedisResult<ConnectionInfo> {
+        self.config.node_info(addr)
+    }
+
+    async fn get_node(&self, addr: &str) -> Result<Node, error::RedisError> {
+        if let Some(node) = self.nodes.read().await.get(addr) {
+            return Ok(node.clone());
+        }
+        let mut nodes = self.nodes.write().await;
+        if let Some(node) = nodes.get(addr) {
+            return Ok(node.clone());
+        }
+        let node = self.create_node(self.node_info(addr)?).await?;
+        nodes.insert(addr.to_string(), node.clone());
+        Ok(node)
+    }
+
+    async fn get_replica(&self, addr: &str) -> Result<Node, error::RedisError> {
+        if let Some(node) = self.replicas.read().await.get(addr) {
+            return Ok(node.clone());
+        }
+        let mut replicas = self.replicas.write().await;
+        if let Some(node) = replicas.get(addr) {
+            return Ok(node.clone());
+        }
+        let node = Node::replica(self.node_info(addr)?, self.node_config()).await?;
+        replicas.insert(addr.to_string(), node.clone());
+        Ok(node)
+    }
+
+    async fn read_node(&self, cmd: &Cmd, params: &Params) -> Option<Node> {
+        if self.config.read_from == ReadFrom::Primary || !is_readonly(cmd) {
+            return None;
+        }
+        let shard = self.slots.read().await.get_route(params).shard?;
+        if shard.slaves.is_empty() {
+            return None;
+        }
+        let n = self.counter.fetch_add(1, Ordering::Relaxed);
+        let count = shard.slaves.len();
+        if self.config.read_from == ReadFrom::RoundRobin && n.is_multiple_of(count + 1) {
+            return None;
+        }
+        // Skip replicas failing health checks, the master serves reads without them
+        for i in 0..count {
+            let addr = &shard.slaves[(n + i) % count];
+            match self.get_replica(addr).await {
+                Ok(node) if node.is_healthy() => return Some(node),
+                _ => continue,
+            }
+        }
+        None
+    }
+
+    /// Split a multi-key command by slot and group the parts by owning node.
+    /// Returns `None` when all keys belong to one slot
+    async fn split(&self, cmd: &Cmd) -> Option<(Merge, Vec<SplitGroup>)> {
+        if !self.is_cluster {
+            return None;
+        }
+        let (merge, parts) = split_by_slot(cmd)?;
+        let slots = self.slots.read().await;
+        let mut groups: HashMap<String, SplitGroup> = HashMap::new();
+        for (positions, part) in parts.into_iter() {
+            let params = Params::from(&part);
+            let addr = slots.get_route(&params).shard?.master;
+            let group = groups.entry(addr.clone()).or_insert_with(|| SplitGroup {
+                addr,
+                ..Default::default()
+            });
+            group.positions.push(positions);
+            group.batch.push((part, params));
+        }
+        Some((merge, groups.into_values().collect()))
+    }
+
+    async fn execute_split(
+        &self,
+        merge: Merge,
+        groups: Vec<SplitGroup>,
+    ) -> Result<redis::Value, error::RedisError> {
+        let mut tasks = tokio::task::JoinSet::new();
+        for group in groups.into_iter() {
+            let node = self.get_node(group.addr.as_str()).await?;
+            let shards = self.clone();
+            tasks.spawn(async move {
+                let values = shards.execute_batch_on_node(&node, group.batch).await?;
+                let parts: Vec<(Vec<usize>, Value)> =
+                    group.positions.into_iter().zip(values).collect();
+                Ok::<_, error::RedisError>(parts)
+            });
+        }
+
+        let mut parts = vec![];
+        while let Some(r) = tasks.join_next().await {
+            parts.extend(r??);
+        }
+        merge_parts(merge, parts)
+    }
+
+    /// Send a node-wide command to every node, the slots are refreshed and the
+    /// command sent again once when a node no longer owns its slots
+    async fn execute_fanout(
+        &self,
+        fanout: Fanout,
+        aggregate_by: Aggregate,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        let values = match self.fanout_once(fanout, &cmd, &params).await {
+            Err(e) if e.redis_error().is_some_and(|e| e.redirect_node().is_some()) => {
+                self.update_slots().await?;
+                self.fanout_once(fanout, &cmd, &params).await?
+            }
+            r => r?,
+        };
+        aggregate(aggregate_by, values)
+    }
+
+    /// Replies of every node in address order
+    async fn fanout_once(
+        &self,
+        fanout: Fanout,
+        cmd: &Cmd,
+        params: &Params,
+    ) -> Result<Vec<(String, Value)>, error::RedisError> {
+        let (masters, replicas) = {
+            let slots = self.slots.read().await;
+            let replicas = match fanout {
+                Fanout::AllMasters => vec![],
+                Fanout::AllNodes => slots.get_replicas(),
+            };
+            (slots.get_masters(), replicas)
+        };
+        let masters = masters.into_iter().map(|addr| (addr, false));
+        let mut addrs: Vec<_> = masters
+            .chain(replicas.into_iter().map(|addr| (addr, true)))
+            .collect();
+        addrs.sort();
+        let mut tasks = tokio::task::JoinSet::new();
+        for (n, (addr, replica)) in addrs.into_iter().enumerate() {
+            let node = match replica {
+                true => self.get_replica(addr.as_str()).await?,
+                false => self.get_node(addr.as_str()).await?,
+            };
+            let (cmd, params) = (cmd.clone(), params.clone());
+            tasks.spawn(async move {
+                let value = node.execute(cmd, params).await?;
+                Ok::<_, error::RedisError>((n, addr, value))
+            });
+        }
+
+        let mut results = vec![];
+        while let Some(r) = tasks.join_next().await {
+            results.push(r??);
+        }
+        results.sort_by_key(|(n, _, _)| *n);
+        let values = results.into_iter().map(|(_, addr, value)| (addr, value));
+        Ok(values.collect())
+    }
+
+    async fn execute_once(
+        &self,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        if let Some((policy, aggregate)) = fanout(&cmd).filter(|_| self.is_cluster) {
+            return self.execute_fanout(policy, aggregate, cmd, params).await;
+        }
+        if let Some((merge, groups)) = self.split(&cmd).await {
+            return self.execute_split(merge, groups).await;
+        }
+        if let Some(node) = self.read_node(&cmd, &params).await {
+            match node.execute(cmd.clone(), params.clone()).await {
+                Err(e) if e.redis_error().is_some_and(|e| e.is_io_error()) => {}
+                r => return self.redirect(cmd, params, r).await,
+            }
+        }
+        let result = match self.send_command(&cmd, &params).await {
+            Err(error::RedisError::NotFoundNode) => {
+                self.send_command_with_add_node(&cmd, &params).await
+            }
+            r => r,
+        };
+        self.redirect(cmd, params, result).await
+    }
+
+    async fn redirect(
+        &self,
+        cmd: Cmd,
+        params: Params,
+        result: Result<redis::Value, error::RedisError>,
+    ) -> Result<redis::Value, error::RedisError> {
+        let max_redirects = self.config.max_redirects.unwrap_or(MAX_REDIRECTS);
+        follow_redirects(self, max_redirects, &cmd, &params, result).await
+    }
+
+    /// Follow a MOVED or ASK reply of a batch entry, other server errors stay
+    /// replies like in the rest of the batch
+    async fn redirect_reply(
+        &self,
+        cmd: Cmd,
+        params: Params,
+        reply: Value,
+    ) -> Result<redis::Value, error::RedisError> {
+        let redirect = match reply {
+            Value::ServerError(ref e) if is_redirect(&reply) => RedisError::from(e.clone()),
+            reply => return Ok(reply),
+        };
+        let result = self.redirect(cmd, params, Err(redirect.into())).await;
+        let Err(e) = result else {
+            return result;
+        };
+        match e
+            .redis_error()
+            .cloned()
+            .and_then(|e| e.into_server_errors())
+        {
+            Some(errors) if errors.len() == 1 => Ok(Value::ServerError(errors[0].1.clone())),
+            _ => Err(e),
+        }
+    }
+
+    /// Send a batch to one node, following the redirects of its entries
+    async fn execute_batch_on_node(
+        &self,
+        node: &Node,
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError> {
+        let values = node.execute_batch(batch.clone()).await?;
+        if !values.iter().any(is_redirect) {
+            return Ok(values);
+        }
+        let mut replies = Vec::with_capacity(values.len());
+        for ((cmd, params), value) in batch.into_iter().zip(values) {
+            replies.push(self.redirect_reply(cmd, params, value).await?);
+        }
+        Ok(replies)
+    }
+}
+
+fn is_redirect(value: &Value) -> bool {
+    matches!(value, Value::ServerError(e)
+        if matches!(e.kind(), Some(ServerErrorKind::Moved | ServerErrorKind::Ask)))
+}
+
+/// Node access needed to follow MOVED and ASK redirections
+#[async_trait]
+trait Redirect {
+    async fn refresh_slots(&self);
+
+    async fn execute_batch_on(
+        &self,
+        addr: &str,
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError>;
+}
+
+#[async_trait]
+impl Redirect for AsyncShards {
+    async fn refresh_slots(&self) {
+        self.update_slots().await.ok();
+    }
+
+    async fn execute_batch_on(
+        &self,
+        addr: &str,
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError> {
+        self.get_node(addr).await?.execute_batch(batch).await
+    }
+}
+
+/// Send the command to the node named by MOVED or ASK, at most `max_redirects` times.
+/// MOVED refreshes the slots first, ASK is a one-off redirection preceded by ASKING
+async fn follow_redirects<R: Redirect + Sync + ?Sized>(
+    r: &R,
+    max_redirects: usize,
+    cmd: &Cmd,
+    params: &Params,
+    mut result: Result<redis::Value, error::RedisError>,
+) -> Result<redis::Value, error::RedisError> {
+    for _ in 0..max_redirects {
+        let (addr, kind) = match result.as_ref().map_err(|e| e.redis_error()) {
+            Err(Some(e)) => match e.redirect_node() {
+                Some((addr, _)) => (addr.to_string(), e.kind()),
+                None => break,
+            },
+            _ => break,
+        };
+        let mut batch = vec![];
+        if kind == ErrorKind::Server(ServerErrorKind::Ask) {
+            batch.push((redis::cmd("ASKING"), Params::default()));
+        } else {
+            r.refresh_slots().await;
+        }
+        batch.push((cmd.clone(), params.clone()));
+        result = match r.execute_batch_on(addr.as_str(), batch).await {
+            Ok(mut values) => values
+                .pop()
+                .unwrap_or(redis::Value::Nil)
+                .extract_error()
+                .map_err(error::RedisError::from),
+            Err(e) => Err(e),
+        }
+        .map_err(|e| e.with_context(|c| c.node = Some(addr)));
+    }
+    result
 }
 
 #[async_trait]
 impl Pool for AsyncShards {
-    async fn execute(&self, cmd: Cmd, _params: Params) -> Result<redis::Value, error::RedisError> {
-        match self.send_command(&cmd).await {
-            Err(error::RedisError::NotFoundNode) => {
-                Ok(self.send_command_with_add_node(&cmd).await?)
+    async fn execute(&self, cmd: Cmd, params: Params) -> Result<redis::Value, error::RedisError> {
+        self.retry
+            .execute(&cmd, || self.execute_once(cmd.clone(), params.clone()))
+            .await
+            .map_err(|e| match params.keys.first().filter(|_| self.is_cluster) {
+                Some(key) => e.with_context(|c| c.slot = Some(slot(key))),
+                None => e,
+            })
+    }
+
+    async fn execute_batch(
+        &self,
+        batch: Vec<(Cmd, Params)>,
+    ) -> Result<Vec<redis::Value>, error::RedisError> {
+        let count = batch.len();
+        let mut groups: HashMap<String, Vec<(usize, Cmd, Params)>> = HashMap::new();
+        {
+            let slots = self.slots.read().await;
+            let nodes = self.nodes.read().await;
+            for (n, (cmd, params)) in batch.into_iter().enumerate() {
+                let addr = if let Some(shard) = slots.get_route(&params).shard {
+                    shard.master
+                } else {
+                    nodes
+                        .keys()
+                        .next()
+                        .cloned()
+                        .ok_or(error::RedisError::NoSlot)?
+                };
+                groups.entry(addr).or_default().push((n, cmd, params));
             }
-            r => r,
+        }
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for (addr, group) in groups.into_iter() {
+            let node = self.get_node(addr.as_str()).await?;
+            let shards = self.clone();
+            tasks.spawn(async move {
+                let (positions, batch): (Vec<usize>, Vec<(Cmd, Params)>) = group
+                    .into_iter()
+                    .map(|(n, cmd, params)| (n, (cmd, params)))
+                    .unzip();
+                let values = shards.execute_batch_on_node(&node, batch).await?;
+                Ok::<_, error::RedisError>(positions.into_iter().zip(values).collect::<Vec<_>>())
+            });
+        }
+
+        let mut result = vec![redis::Value::Nil; count];
+        while let Some(r) = tasks.join_next().await {
+            for (n, value) in r??.into_iter() {
+                result[n] = value;
+            }
+        }
+        Ok(result)
+    }
+
+    async fn get_connection(
+        &self,
+        params: Params,
+    ) -> Result<Box<dyn Connection + Send + Sync>, error::RedisError> {
+        let addr = if let Some(shard) = self.slots.read().await.get_route(&params).shard {
+            shard.master
+        } else {
+            self.nodes
+                .read()
+                .await
+                .keys()
+                .next()
+                .cloned()
+                .ok_or(error::RedisError::NoSlot)?
+        };
+        let node = self.get_node(addr.as_str()).await?;
+        let mut conn = ShardConnection {
+            conn: node.get_connection(params.clone()).await?,
+            addr,
+            slots: self.is_cluster.then(|| self.slots.clone()),
+            slot: None,
+        };
+        conn.check(&params).await?;
+        Ok(Box::new(conn))
+    }
+
+    async fn masters(&self) -> Result<Vec<String>, error::RedisError> {
+        if self.is_cluster {
+            Ok(self.slots.read().await.get_masters())
+        } else {
+            Ok(self.nodes.read().await.keys().cloned().collect())
         }
     }
 
-    fn status(&self) -> HashMap<&str, redis::Value> {
+    async fn node_addr(&self, cmd: &Cmd, params: &Params) -> Option<String> {
+        // fan-out, split and replica reads are spread over several nodes
+        let spread = self.is_cluster && (fanout(cmd).is_some() || params.keys.len() > 1);
+        if spread || (self.config.read_from != ReadFrom::Primary && is_readonly(cmd)) {
+            return None;
+        }
+        // keyless commands go to any node, the slot table is only read for keyed ones
+        let shard = match params.keys.is_empty() {
+            true => None,
+            false => self.slots.read().await.get_route(params).shard,
+        };
+        match shard {
+            Some(shard) => Some(shard.master),
+            None => self.nodes.read().await.keys().next().cloned(),
+        }
+    }
+
+    async fn execute_on(
+        &self,
+        addr: &str,
+        cmd: Cmd,
+        params: Params,
+    ) -> Result<redis::Value, error::RedisError> {
+        self.get_node(addr).await?.execute(cmd, params).await
+    }
+
+    async fn subscribe(
+        &self,
+        channels: Vec<Vec<u8>>,
+        patterns: Vec<Vec<u8>>,
+    ) -> Result<pubsub::Receiver, error::RedisError> {
+        let node = self.nodes.read().await.values().next().cloned();
+        let node = node.ok_or(error::RedisError::NotFoundNode)?;
+        node.subscribe(channels, patterns).await
+    }
+
+    fn status(&self) -> HashMap<&'static str, redis::Value> {
         let mut result = HashMap::new();
         result.insert("closed", redis::Value::Boolean(false));
         result.insert("impl", redis::Value::SimpleString("shards_async".into()));
         result.insert("cluster", redis::Value::Boolean(self.is_cluster));
+        let read_from = format!("{:?}", self.config.read_from);
+        result.insert("read_from", redis::Value::SimpleString(read_from));
         if let Ok(nodes) = self.nodes.try_read() {
             let mut addrs: Vec<String> = nodes.keys().cloned().collect();
             addrs.sort();
@@ -168,6 +836,510 @@ impl Pool for AsyncShards {
                 redis::Value::Array(addrs.into_iter().map(redis::Value::SimpleString).collect());
             result.insert("nodes", addrs);
         }
+        if let (Ok(nodes), Ok(replicas)) = (self.nodes.try_read(), self.replicas.try_read()) {
+            let mut health: Vec<(String, redis::Value)> = nodes
+                .iter()
+                .chain(replicas.iter())
+                .map(|(addr, node)| (addr.clone(), node.health()))
+                .collect();
+            health.sort_by(|a, b| a.0.cmp(&b.0));
+            let health = health
+                .into_iter()
+                .map(|(addr, h)| (redis::Value::SimpleString(addr), h))
+                .collect();
+            result.insert("health", redis::Value::Map(health));
+        }
+        self.retry.status(&mut result);
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use redis::Value;
+
+    use std::{
+        collections::VecDeque,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc, Mutex, OnceLock,
+        },
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use redis::{Cmd, ErrorKind, IntoConnectionInfo, ServerErrorKind};
+
+    use crate::{
+        command::{command_name, Aggregate, Merge, Params},
+        config::{Config, HealthCheck, ReadFrom},
+        error, fake,
+        pool::{Connection, Pool},
+    };
+
+    use super::{
+        aggregate, follow_redirects, merge_parts, split_by_slot, AsyncShards, Redirect,
+        ShardConnection,
+    };
+
+    /// Shards of a fake master owning every slot with one replica served by `replica`
+    async fn fake_cluster<F>(replica: F, config: Config) -> AsyncShards
+    where
+        F: Fn(&[Vec<u8>]) -> Vec<u8> + Clone + Send + Sync + 'static,
+    {
+        let replica = fake::serve(replica).await;
+        let master = Arc::new(OnceLock::<String>::new());
+        let addr = fake::serve({
+            let master = master.clone();
+            move |cmd| {
+                let addr = master.get().unwrap();
+                match (fake::name(cmd).as_str(), cmd.get(1).map(|a| a.as_slice())) {
+                    ("CLUSTER", Some(b"SLOTS")) => fake::slots(addr, &[replica.as_str()]),
+                    ("CLUSTER", _) => fake::bulk(addr.as_bytes()),
+                    ("GET", _) => fake::bulk(b"master"),
+                    ("SCRIPT", Some(b"EXISTS")) => b"*2\r\n:1\r\n:1\r\n".to_vec(),
+                    _ => fake::ok(),
+                }
+            }
+        })
+        .await;
+        master.set(addr.clone()).unwrap();
+        let config = Config {
+            initial_nodes: vec![format!("redis://{addr}").into_connection_info().unwrap()],
+            max_size: 1,
+            read_from: ReadFrom::PreferReplica,
+            ..config
+        };
+        AsyncShards::new(config).await.unwrap()
+    }
+
+    async fn get(shards: &AsyncShards) -> Value {
+        let cmd = redis::cmd("GET").arg("a").to_owned();
+        shards
+            .execute(cmd.clone(), Params::from(&cmd))
+            .await
+            .unwrap()
+    }
+
+    /// Node replies given in order, with the commands sent and the slot refreshes
+    #[derive(Default)]
+    struct Redirects {
+        replies: Mutex<VecDeque<Value>>,
+        sent: Mutex<Vec<String>>,
+        refreshed: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Redirect for Redirects {
+        async fn refresh_slots(&self) {
+            self.refreshed.fetch_add(1, Ordering::Relaxed);
+        }
+
+        async fn execute_batch_on(
+            &self,
+            addr: &str,
+            batch: Vec<(Cmd, Params)>,
+        ) -> Result<Vec<Value>, error::RedisError> {
+            let mut values = vec![];
+            for (cmd, _) in batch.iter() {
+                let name = command_name(cmd);
+                let value = match name.as_str() {
+                    "ASKING" => Value::Okay,
+                    _ => self.replies.lock().unwrap().pop_front().unwrap(),
+                };
+                self.sent.lock().unwrap().push(format!("{addr} {name}"));
+                values.push(value);
+            }
+            Ok(values)
+        }
+    }
+
+    fn reply(data: &[u8]) -> Value {
+        redis::parse_redis_value(data).unwrap()
+    }
+
+    fn server_error(data: &[u8]) -> Result<Value, error::RedisError> {
+        Err(reply(data).extract_error().unwrap_err().into())
+    }
+
+    async fn redirect(
+        r: &Redirects,
+        result: Result<Value, error::RedisError>,
+    ) -> Result<Value, error::RedisError> {
+        let cmd = redis::cmd("GET").arg("a").to_owned();
+        follow_redirects(r, 2, &cmd, &Params::from(&cmd), result).await
+    }
+
+    fn replies(values: Vec<Value>) -> Vec<(String, Value)> {
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(n, v)| (format!("node:{n}"), v))
+            .collect()
+    }
+
+    #[test]
+    fn aggregate_sum() {
+        let values = replies(vec![Value::Int(2), Value::Int(3)]);
+        assert_eq!(aggregate(Aggregate::Sum, values).unwrap(), Value::Int(5));
+    }
+
+    #[test]
+    fn aggregate_concat() {
+        let a = Value::BulkString(b"a".to_vec());
+        let b = Value::BulkString(b"b".to_vec());
+        let values = replies(vec![
+            Value::Array(vec![a.clone()]),
+            Value::Set(vec![b.clone()]),
+        ]);
+        assert_eq!(
+            aggregate(Aggregate::Concat, values).unwrap(),
+            Value::Array(vec![a, b])
+        );
+    }
+
+    #[test]
+    fn aggregate_all_ok() {
+        let values = replies(vec![Value::Okay, Value::Okay]);
+        assert_eq!(aggregate(Aggregate::AllOk, values).unwrap(), Value::Okay);
+        let sha = Value::BulkString(b"e0e1f9fabfc9d4800c877a703b823ac0578ff8db".to_vec());
+        let values = replies(vec![sha.clone(), sha.clone()]);
+        assert_eq!(aggregate(Aggregate::AllOk, values).unwrap(), sha);
+    }
+
+    #[test]
+    fn aggregate_all_ok_error() {
+        let error = redis::parse_redis_value(b"-READONLY replica\r\n").unwrap();
+        let values = replies(vec![Value::Okay, error]);
+        let e = aggregate(Aggregate::AllOk, values).unwrap_err();
+        assert_eq!(e.context().unwrap().node.as_deref(), Some("node:1"));
+        let values = replies(vec![Value::Okay, Value::Nil]);
+        let e = aggregate(Aggregate::AllOk, values).unwrap_err();
+        assert!(matches!(e, error::RedisError::CommandError(_)));
+    }
+
+    #[test]
+    fn aggregate_and() {
+        let flags = |f: &[i64]| Value::Array(f.iter().map(|i| Value::Int(*i)).collect());
+        let values = replies(vec![flags(&[1, 1, 0]), flags(&[1, 0, 0])]);
+        assert_eq!(
+            aggregate(Aggregate::And, values).unwrap(),
+            flags(&[1, 0, 0])
+        );
+    }
+
+    #[tokio::test]
+    async fn redirect_moved() {
+        let r = Redirects::default();
+        r.replies.lock().unwrap().push_back(reply(b"$1\r\n1\r\n"));
+        let result = redirect(&r, server_error(b"-MOVED 15495 127.0.0.1:7002\r\n")).await;
+        assert_eq!(result.unwrap(), Value::BulkString(b"1".to_vec()));
+        assert_eq!(*r.sent.lock().unwrap(), ["127.0.0.1:7002 GET"]);
+        assert_eq!(r.refreshed.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn redirect_ask() {
+        let r = Redirects::default();
+        r.replies.lock().unwrap().push_back(reply(b"$1\r\n1\r\n"));
+        let result = redirect(&r, server_error(b"-ASK 15495 127.0.0.1:7002\r\n")).await;
+        assert_eq!(result.unwrap(), Value::BulkString(b"1".to_vec()));
+        assert_eq!(
+            *r.sent.lock().unwrap(),
+            ["127.0.0.1:7002 ASKING", "127.0.0.1:7002 GET"]
+        );
+        assert_eq!(r.refreshed.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn redirect_max() {
+        let r = Redirects::default();
+        for port in [7002, 7003, 7004] {
+            let moved = format!("-MOVED 15495 127.0.0.1:{port}\r\n");
+            r.replies.lock().unwrap().push_back(reply(moved.as_bytes()));
+        }
+        let result = redirect(&r, server_error(b"-MOVED 15495 127.0.0.1:7001\r\n")).await;
+        let e = result.unwrap_err();
+        assert_eq!(
+            e.redis_error().map(|e| e.kind()),
+            Some(ErrorKind::Server(ServerErrorKind::Moved))
+        );
+        assert_eq!(e.context().unwrap().node.as_deref(), Some("127.0.0.1:7002"));
+        assert_eq!(r.sent.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn split_mget() {
+        // the hash tag puts the first and last key in one slot
+        let cmd = redis::cmd("mget")
+            .arg("{a}1")
+            .arg("b")
+            .arg("{a}2")
+            .to_owned();
+        let (merge, parts) = split_by_slot(&cmd).unwrap();
+        assert_eq!(merge, Merge::Values);
+        let mut parts: Vec<(Vec<usize>, Vec<u8>)> = parts
+            .into_iter()
+            .map(|(positions, part)| (positions, part.get_packed_command()))
+            .collect();
+        parts.sort();
+        assert_eq!(
+            parts,
+            vec![
+                (
+                    vec![0, 2],
+                    redis::cmd("MGET")
+                        .arg("{a}1")
+                        .arg("{a}2")
+                        .get_packed_command()
+                ),
+                (vec![1], redis::cmd("MGET").arg("b").get_packed_command()),
+            ]
+        );
+        let cmd = redis::cmd("MGET").arg("{a}1").arg("{a}2").to_owned();
+        assert!(split_by_slot(&cmd).is_none());
+    }
+
+    #[test]
+    fn merge_values() {
+        let value = |v: &[u8]| Value::BulkString(v.to_vec());
+        let parts = vec![
+            (vec![1], Value::Array(vec![value(b"b")])),
+            (vec![0, 2], Value::Array(vec![value(b"a"), Value::Nil])),
+        ];
+        assert_eq!(
+            merge_parts(Merge::Values, parts).unwrap(),
+            Value::Array(vec![value(b"a"), value(b"b"), Value::Nil])
+        );
+    }
+
+    #[test]
+    fn merge_sum() {
+        let parts = vec![(vec![0, 2], Value::Int(2)), (vec![1], Value::Int(1))];
+        assert_eq!(merge_parts(Merge::Sum, parts).unwrap(), Value::Int(3));
+    }
+
+    #[test]
+    fn merge_partial_failure() {
+        let error = reply(b"-OOM command not allowed\r\n");
+        let parts = vec![(vec![0], Value::Okay), (vec![1], error.clone())];
+        assert!(merge_parts(Merge::AllOk, parts).is_err());
+        let parts = vec![(vec![0], Value::Int(1)), (vec![1], error)];
+        assert!(merge_parts(Merge::Sum, parts).is_err());
+        let parts = vec![(vec![0], Value::Okay), (vec![1], Value::Nil)];
+        assert!(merge_parts(Merge::AllOk, parts).is_err());
+    }
+
+    struct Echo;
+
+    #[async_trait]
+    impl Connection for Echo {
+        async fn execute(
+            &mut self,
+            _cmd: Cmd,
+            _params: Params,
+        ) -> Result<Value, error::RedisError> {
+            Ok(Value::Okay)
+        }
+
+        async fn exec(&mut self, _batch: Vec<(Cmd, Params)>) -> Result<Value, error::RedisError> {
+            Ok(Value::Array(vec![]))
+        }
+    }
+
+    #[tokio::test]
+    async fn transaction_cross_slot() {
+        let mut conn = ShardConnection {
+            conn: Box::new(Echo),
+            addr: "127.0.0.1:7000".into(),
+            slots: Some(Default::default()),
+            slot: None,
+        };
+        let cmd = |name: &str, key: &str| {
+            let cmd = redis::cmd(name).arg(key).to_owned();
+            let params = Params::from(&cmd);
+            (cmd, params)
+        };
+        let (ping, params) = (redis::cmd("PING"), Params::default());
+        assert!(conn.execute(ping, params).await.is_ok());
+        // the first keyed command pins the slot
+        let (get, params) = cmd("GET", "{a}1");
+        assert!(conn.execute(get, params).await.is_ok());
+        let (get, params) = cmd("GET", "b");
+        let e = conn.execute(get, params).await.unwrap_err();
+        assert!(matches!(e, error::RedisError::CommandError(ref m) if m.starts_with("CROSSSLOT")));
+        let e = conn.exec(vec![cmd("SET", "{a}2"), cmd("SET", "b")]).await;
+        assert!(e.is_err());
+        assert!(conn.exec(vec![cmd("SET", "{a}2")]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn replica_unhealthy() {
+        let replica = |cmd: &[Vec<u8>]| match fake::name(cmd).as_str() {
+            "PING" => fake::error("ERR down"),
+            "GET" => fake::bulk(b"replica"),
+            _ => fake::ok(),
+        };
+        let config = Config {
+            health_check: Some(HealthCheck {
+                interval: Duration::from_millis(50),
+                failures: 1,
+            }),
+            ..Default::default()
+        };
+        let shards = fake_cluster(replica, config).await;
+        assert_eq!(get(&shards).await, Value::BulkString(b"replica".to_vec()));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(get(&shards).await, Value::BulkString(b"master".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn read_replica_readonly() {
+        // the replica redirects reads until the connection sent READONLY
+        let readonly = Arc::new(AtomicBool::new(false));
+        let sent = Arc::new(AtomicUsize::new(0));
+        let drop = Arc::new(AtomicBool::new(false));
+        let replica = {
+            let (readonly, sent, drop) = (readonly.clone(), sent.clone(), drop.clone());
+            move |cmd: &[Vec<u8>]| match fake::name(cmd).as_str() {
+                "READONLY" => {
+                    readonly.store(true, Ordering::Relaxed);
+                    sent.fetch_add(1, Ordering::Relaxed);
+                    fake::ok()
+                }
+                _ if drop.swap(false, Ordering::Relaxed) => {
+                    readonly.store(false, Ordering::Relaxed);
+                    vec![]
+                }
+                "GET" if readonly.load(Ordering::Relaxed) => fake::bulk(b"replica"),
+                "GET" => fake::error("MOVED 15495 127.0.0.1:1"),
+                "SET" => fake::error("READONLY You can't write against a read only replica."),
+                _ => fake::ok(),
+            }
+        };
+        let shards = fake_cluster(replica, Config::default()).await;
+        assert_eq!(get(&shards).await, Value::BulkString(b"replica".to_vec()));
+        let cmd = redis::cmd("SET").arg("a").arg(1).to_owned();
+        let value = shards.execute(cmd.clone(), Params::from(&cmd)).await;
+        assert_eq!(value.unwrap(), Value::Okay);
+
+        // the reconnected connection has to send READONLY again
+        let before = sent.load(Ordering::Relaxed);
+        drop.store(true, Ordering::Relaxed);
+        let cmd = redis::cmd("GET").arg("a").to_owned();
+        let _ = shards.execute(cmd.clone(), Params::from(&cmd)).await;
+        assert_eq!(get(&shards).await, Value::BulkString(b"replica".to_vec()));
+        assert!(sent.load(Ordering::Relaxed) > before);
+    }
+
+    #[tokio::test]
+    async fn fanout_all_nodes() {
+        let replica = |cmd: &[Vec<u8>]| match fake::name(cmd).as_str() {
+            "INFO" => fake::bulk(b"replica"),
+            _ => fake::ok(),
+        };
+        let shards = fake_cluster(replica, Config::default()).await;
+        let cmd = redis::cmd("INFO").arg("SERVER").to_owned();
+        let value = shards.execute(cmd.clone(), Params::from(&cmd)).await;
+        let Value::Map(nodes) = value.unwrap() else {
+            panic!("INFO is not aggregated by node");
+        };
+        let mut values: Vec<_> = nodes.into_iter().map(|(_, v)| v).collect();
+        values.sort_by_key(|v| format!("{v:?}"));
+        assert_eq!(
+            values,
+            [Value::BulkString(b"replica".to_vec()), Value::Okay]
+        );
+    }
+
+    #[tokio::test]
+    async fn fanout_script_exists() {
+        // the replica misses the second script
+        let replica = |cmd: &[Vec<u8>]| match fake::name(cmd).as_str() {
+            "SCRIPT" => b"*2\r\n:1\r\n:0\r\n".to_vec(),
+            _ => fake::ok(),
+        };
+        let shards = fake_cluster(replica, Config::default()).await;
+        let cmd = redis::cmd("SCRIPT")
+            .arg("EXISTS")
+            .arg("a")
+            .arg("b")
+            .to_owned();
+        let value = shards.execute(cmd.clone(), Params::from(&cmd)).await;
+        assert_eq!(
+            value.unwrap(),
+            Value::Array(vec![Value::Int(1), Value::Int(0)])
+        );
+    }
+
+    /// Shards of two fake masters, the slots move from `a` to `b` once `moved` is set
+    async fn resharded_cluster(moved: Arc<AtomicBool>) -> AsyncShards {
+        let addrs = Arc::new(OnceLock::<(String, String)>::new());
+        let handler = |name: &'static str| {
+            let (addrs, moved) = (addrs.clone(), moved.clone());
+            move |cmd: &[Vec<u8>]| {
+                let (a, b) = addrs.get().unwrap();
+                let me = if name == "a" { a } else { b };
+                let owner = if moved.load(Ordering::Relaxed) { b } else { a };
+                match (fake::name(cmd).as_str(), cmd.get(1).map(|a| a.as_slice())) {
+                    ("CLUSTER", Some(b"SLOTS")) => fake::slots(owner, &[]),
+                    ("CLUSTER", _) => fake::bulk(me.as_bytes()),
+                    _ if me != owner => fake::error(&format!("MOVED 0 {owner}")),
+                    ("GET", _) => fake::bulk(name.as_bytes()),
+                    ("MGET", _) => {
+                        let mut reply = format!("*{}\r\n", cmd.len() - 1).into_bytes();
+                        for _ in 1..cmd.len() {
+                            reply.extend(fake::bulk(name.as_bytes()));
+                        }
+                        reply
+                    }
+                    ("DBSIZE", _) => b":2\r\n".to_vec(),
+                    _ => fake::ok(),
+                }
+            }
+        };
+        let a = fake::serve(handler("a")).await;
+        let b = fake::serve(handler("b")).await;
+        addrs.set((a.clone(), b)).unwrap();
+        let config = Config {
+            initial_nodes: vec![format!("redis://{a}").into_connection_info().unwrap()],
+            max_size: 1,
+            ..Default::default()
+        };
+        AsyncShards::new(config).await.unwrap()
+    }
+
+    /// Give the slots back to `a` in the slot table, then move them to `b` again
+    async fn reshard(shards: &AsyncShards, moved: &AtomicBool) {
+        moved.store(false, Ordering::Relaxed);
+        shards.update_slots().await.unwrap();
+        moved.store(true, Ordering::Relaxed);
+    }
+
+    #[tokio::test]
+    async fn redirect_batch_split_fanout() {
+        let moved = Arc::new(AtomicBool::new(false));
+        let shards = resharded_cluster(moved.clone()).await;
+        let b = Value::BulkString(b"b".to_vec());
+        moved.store(true, Ordering::Relaxed);
+
+        let get = |key: &str| {
+            let cmd = redis::cmd("GET").arg(key).to_owned();
+            let params = Params::from(&cmd);
+            (cmd, params)
+        };
+        let values = shards.execute_batch(vec![get("x"), get("y")]).await;
+        assert_eq!(values.unwrap(), [b.clone(), b.clone()]);
+
+        reshard(&shards, &moved).await;
+        let cmd = redis::cmd("MGET").arg("x").arg("y").to_owned();
+        let value = shards.execute(cmd.clone(), Params::from(&cmd)).await;
+        assert_eq!(value.unwrap(), Value::Array(vec![b.clone(), b]));
+
+        reshard(&shards, &moved).await;
+        // the node that lost its slots redirects, the refreshed masters answer
+        let cmd = redis::cmd("DBSIZE").to_owned();
+        let value = shards.execute(cmd.clone(), Params::from(&cmd)).await;
+        assert_eq!(value.unwrap(), Value::Int(2));
+    }
+}
@@ -0,0 +1,146 @@
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict, IntoPyObjectExt};
+use redis::{FromRedisValue, Value};
+
+use crate::types;
+
+/// Stream message returned by `xread(..., entries=True)`
+#[pyclass]
+pub struct StreamEntry {
+    #[pyo3(get)]
+    stream: String,
+    #[pyo3(get)]
+    id: String,
+    #[pyo3(get)]
+    fields: Py<PyDict>,
+}
+
+/// Millisecond and sequence parts of a message id, the sequence is None for `ms-*`
+fn split_id(id: &str) -> Option<(u64, Option<u64>)> {
+    let (ms, seq) = id.split_once('-').unwrap_or((id, "0"));
+    let seq = match seq {
+        "*" => None,
+        seq => Some(seq.parse().ok()?),
+    };
+    Some((ms.parse().ok()?, seq))
+}
+
+#[pymethods]
+impl StreamEntry {
+    #[getter]
+    fn ms(&self) -> PyResult<u64> {
+        Ok(Self::parse_id(types::Arg::String(self.id.clone()))?.0)
+    }
+
+    #[getter]
+    fn seq(&self) -> PyResult<u64> {
+        Ok(Self::parse_id(types::Arg::String(self.id.clone()))?
+            .1
+            .unwrap_or_default())
+    }
+
+    /// Parse an id given like `xadd` accepts it: "ms-seq", "ms-*", int or float
+    #[staticmethod]
+    fn parse_id(id: types::Arg) -> PyResult<(u64, Option<u64>)> {
+        id.to_normalized_stream_msg_id()
+            .ok()
+            .and_then(|id| split_id(&id))
+            .ok_or_else(|| PyValueError::new_err(format!("Invalid stream id: {id:?}")))
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "StreamEntry(stream={:?}, id={:?}, fields={})",
+            self.stream,
+            self.id,
+            self.fields.bind(py).repr()?
+        ))
+    }
+}
+
+fn to_string(value: Value) -> PyResult<String> {
+    String::from_redis_value(value).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Stream name and messages, from a RESP3 map or RESP2 nested or flat arrays
+fn streams(value: Value) -> Vec<(Value, Value)> {
+    match value {
+        Value::Map(streams) => streams,
+        Value::Array(items)
+            if items
+                .iter()
+                .all(|i| matches!(i, Value::Array(a) if a.len() == 2)) =>
+        {
+            items
+                .into_iter()
+                .filter_map(|item| match item {
+                    Value::Array(pair) => {
+                        let mut pair = pair.into_iter();
+                        Some((pair.next()?, pair.next()?))
+                    }
+                    _ => None,
+                })
+                .collect()
+        }
+        Value::Array(items) => {
+            let mut items = items.into_iter();
+            let mut result = vec![];
+            while let (Some(stream), Some(messages)) = (items.next(), items.next()) {
+                result.push((stream, messages));
+            }
+            result
+        }
+        _ => vec![],
+    }
+}
+
+/// XREAD reply as a list of `StreamEntry` in the order sent by the server
+pub fn to_entries(py: Python, value: Value, codec: types::Codec) -> PyResult<Py<PyAny>> {
+    let mut entries = vec![];
+    for (stream, messages) in streams(value) {
+        let stream = to_string(stream)?;
+        let Value::Array(messages) = messages else {
+            continue;
+        };
+        for message in messages {
+            let Value::Array(message) = message else {
+                continue;
+            };
+            let mut message = message.into_iter();
+            let id = to_string(message.next().unwrap_or(Value::Nil))?;
+            let fields = PyDict::new(py);
+            let values = match message.next() {
+                Some(Value::Array(values)) => values,
+                Some(Value::Map(values)) => values.into_iter().flat_map(|(k, v)| [k, v]).collect(),
+                _ => vec![],
+            };
+            let mut values = values.into_iter();
+            while let (Some(k), Some(v)) = (values.next(), values.next()) {
+                fields.set_item(to_string(k)?, types::to_object(py, v, codec.clone())?)?;
+            }
+            let entry = StreamEntry {
+                stream: stream.clone(),
+                id,
+                fields: fields.unbind(),
+            };
+            entries.push(Py::new(py, entry)?);
+        }
+    }
+    entries.into_py_any(py)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_id;
+
+    #[test]
+    fn split() {
+        assert_eq!(
+            split_id("1526919030474-55"),
+            Some((1526919030474, Some(55)))
+        );
+        assert_eq!(split_id("1-*"), Some((1, None)));
+        assert_eq!(split_id("12"), Some((12, Some(0))));
+        assert_eq!(split_id("*"), None);
+        assert_eq!(split_id("a-1"), None);
+    }
+}
@@ -0,0 +1,211 @@
+use std::sync::Arc;
+
+use pyo3::{exceptions::PyStopAsyncIteration, prelude::*, IntoPyObjectExt};
+use redis::{Cmd, Value};
+
+use crate::{
+    client_result_async::{tokio_rt, AsyncClientResult},
+    command::Params,
+    error,
+    exceptions::WatchError,
+    pool::Connection,
+    types,
+};
+
+struct State {
+    watch: Vec<Vec<u8>>,
+    retries: usize,
+    conn: Option<Box<dyn Connection + Send + Sync>>,
+    batch: Vec<(Cmd, Params)>,
+    attempts: usize,
+    executed: bool,
+    done: bool,
+}
+
+impl State {
+    async fn connect(&mut self, cr: &AsyncClientResult, params: Params) -> PyResult<()> {
+        if self.conn.is_none() {
+            self.conn = Some(cr.get_connection(params).await?);
+        }
+        Ok(())
+    }
+
+    async fn send(&mut self, cr: &AsyncClientResult, cmd: Cmd, params: Params) -> PyResult<Value> {
+        self.connect(cr, params.clone()).await?;
+        let mut conn = self.conn.take().unwrap();
+        let cm = cr.cm.clone();
+        let (conn, result) = tokio_rt()
+            .spawn(async move {
+                let result = cm
+                    .read()
+                    .await
+                    .query_connection(conn.as_mut(), cmd, params)
+                    .await;
+                (conn, result)
+            })
+            .await
+            .unwrap();
+        self.conn = Some(conn);
+        Ok(result?)
+    }
+}
+
+#[pyclass(skip_from_py_object)]
+#[derive(Clone)]
+pub struct Transaction {
+    cr: AsyncClientResult,
+    state: Arc<tokio::sync::Mutex<State>>,
+}
+
+impl Transaction {
+    pub(crate) fn new(cr: AsyncClientResult, watch: Vec<Vec<u8>>, retries: usize) -> Self {
+        let state = State {
+            watch,
+            retries,
+            conn: None,
+            batch: vec![],
+            attempts: 0,
+            executed: false,
+            done: false,
+        };
+        Self {
+            cr,
+            state: Arc::new(tokio::sync::Mutex::new(state)),
+        }
+    }
+}
+
+#[pymethods]
+impl Transaction {
+    fn __aiter__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __anext__(slf: Bound<'_, Self>) -> PyResult<Bound<'_, PyAny>> {
+        let stop = {
+            let tx = slf.borrow();
+            let state = tx.state.try_lock().map_err(error::RedisError::from)?;
+            state.done || state.attempts > state.retries
+        };
+        if stop {
+            return Err(PyStopAsyncIteration::new_err(()));
+        }
+        slf.call_method0("_attempt")
+    }
+
+    async fn _attempt(&self) -> Self {
+        self.clone()
+    }
+
+    async fn __aenter__(&self) -> PyResult<Self> {
+        let mut state = self.state.lock().await;
+        state.attempts += 1;
+        state.executed = false;
+        state.batch.clear();
+        if !state.watch.is_empty() {
+            let cmd = redis::cmd("WATCH").arg(&state.watch).to_owned();
+            let params = Params {
+                keys: state.watch.clone(),
+                ..Default::default()
+            };
+            state.send(&self.cr, cmd, params).await?;
+        }
+        Ok(self.clone())
+    }
+
+    async fn __aexit__(
+        &self,
+        _exc_type: Py<PyAny>,
+        exc_value: Py<PyAny>,
+        _traceback: Py<PyAny>,
+    ) -> PyResult<bool> {
+        let mut state = self.state.lock().await;
+        if !state.executed && state.conn.is_some() {
+            let cmd = redis::cmd("UNWATCH");
+            state.send(&self.cr, cmd, Params::default()).await.ok();
+        }
+        state.conn = None;
+        let aborted = Python::attach(|py| exc_value.bind(py).is_instance_of::<WatchError>());
+        if aborted && state.attempts <= state.retries {
+            return Ok(true);
+        }
+        state.done = true;
+        Ok(false)
+    }
+
+    #[pyo3(signature = (cmd, *args, encoding = None))]
+    async fn execute(
+        &self,
+        cmd: types::Str,
+        args: Vec<types::Arg>,
+        encoding: Option<String>,
+    ) -> PyResult<Py<PyAny>> {
+        let cmd = String::from(cmd).to_ascii_uppercase();
+        let cmd = redis::cmd(cmd.as_str()).arg(args).to_owned();
+        let mut params = self.cr.params(&cmd);
+        params.codec = encoding.into();
+        let codec = params.codec.clone();
+        let value = self.state.lock().await.send(&self.cr, cmd, params).await?;
+        Python::attach(|py| types::to_object(py, value, codec))
+    }
+
+    #[pyo3(signature = (cmd, *args, encoding = None))]
+    fn add(
+        slf: PyRef<'_, Self>,
+        cmd: types::Str,
+        args: Vec<types::Arg>,
+        encoding: Option<String>,
+    ) -> PyResult<PyRef<'_, Self>> {
+        let cmd = String::from(cmd).to_ascii_uppercase();
+        let cmd = redis::cmd(cmd.as_str()).arg(args).to_owned();
+        let mut params = slf.cr.params(&cmd);
+        params.codec = encoding.into();
+        slf.state
+            .try_lock()
+            .map_err(error::RedisError::from)?
+            .batch
+            .push((cmd, params));
+        Ok(slf)
+    }
+
+    fn __len__(&self) -> PyResult<usize> {
+        let state = self.state.try_lock().map_err(error::RedisError::from)?;
+        Ok(state.batch.len())
+    }
+
+    async fn exec(&self) -> PyResult<Py<PyAny>> {
+        let mut state = self.state.lock().await;
+        let batch = std::mem::take(&mut state.batch);
+        let keys = batch.iter().flat_map(|(_, p)| p.keys.clone()).collect();
+        let params = Params {
+            keys,
+            ..Default::default()
+        };
+        state.connect(&self.cr, params).await?;
+        let encodings: Vec<types::Codec> = batch.iter().map(|(_, p)| p.codec.clone()).collect();
+        let mut conn = state.conn.take().unwrap();
+        let cm = self.cr.cm.clone();
+        let (conn, result) = tokio_rt()
+            .spawn(async move {
+                let result = cm.read().await.exec_connection(conn.as_mut(), batch).await;
+                (conn, result)
+            })
+            .await
+            .unwrap();
+        state.conn = Some(conn);
+        state.executed = true;
+        match result? {
+            Value::Nil => Err(WatchError::new_err(
+                "Transaction aborted, watched keys changed",
+            )),
+            Value::Array(values) => Python::attach(|py| {
+                let mut result = vec![];
+                for (value, encoding) in values.into_iter().zip(encodings) {
+                    result.push(types::to_object(py, value, encoding)?);
+                }
+                pyo3::types::PyList::new(py, result)?.into_py_any(py)
+            }),
+            value => Python::attach(|py| types::to_object(py, value, types::Codec::default())),
+        }
+    }
+}
@@ -1,13 +1,16 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use pyo3::{
     prelude::*,
-    types::{PyBytes, PyDict, PyDictMethods, PyList, PySet, PySetMethods},
+    types::{
+        PyBool, PyBytes, PyDict, PyDictMethods, PyFloat, PyInt, PyList, PySet, PySetMethods,
+        PyString, PyTuple,
+    },
     FromPyObject, IntoPyObjectExt, PyAny, PyResult, Python,
 };
 use redis::{FromRedisValue, RedisWrite, ToRedisArgs, Value};
 
-use crate::error;
+use crate::{codecs, error};
 
 #[derive(Clone, Default, PartialEq, Eq, Debug)]
 pub enum Codec {
@@ -18,6 +21,7 @@ pub enum Codec {
     Float,
     Info,
     Json,
+    Custom(String),
 }
 
 impl From<&str> for Codec {
@@ -28,6 +32,7 @@ impl From<&str> for Codec {
             "int" => Codec::Int,
             "info" => Codec::Info,
             "json" => Codec::Json,
+            name if codecs::is_registered(name) => Codec::Custom(name.to_string()),
             _ => Codec::Bytes,
         }
     }
@@ -87,6 +92,7 @@ fn _decode(py: Python, v: Vec<u8>, codec: Codec) -> Result<Py<PyAny>, error::Val
                 serde_json::from_slice(&v).map_err(error::ValueError::from)?;
             from_json(py, v)
         }
+        Codec::Custom(_) => unreachable!("custom codecs are decoded by decode"),
     }
 }
 
@@ -121,17 +127,123 @@ fn from_json(py: Python<'_>, v: serde_json::Value) -> Result<Py<PyAny>, error::V
     })
 }
 
-pub fn decode(py: Python, v: Vec<u8>, codec: Codec) -> Py<PyAny> {
-    if let Ok(result) = _decode(py, v, codec) {
-        result
+/// Seconds given from Python, negative and non-finite values raise ValueError
+pub fn duration(name: &str, seconds: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds).map_err(|_| {
+        pyo3::exceptions::PyValueError::new_err(format!(
+            "{name} must be a finite number of seconds >= 0, got {seconds}"
+        ))
+    })
+}
+
+/// Bytes of an outgoing value, serialized by the registered codec for custom encodings
+pub fn encode(value: &Bound<PyAny>, codec: &Codec) -> PyResult<Vec<u8>> {
+    match codec {
+        Codec::Custom(name) => codecs::get(name)?.encode(value),
+        Codec::Json => serde_json::to_vec(&to_json(value)?)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string())),
+        _ => Ok(value.extract::<Arg>()?.to_vec()),
+    }
+}
+
+/// Field-value pairs given flat or as dicts, values encoded by the codec
+pub fn encode_pairs<'py>(
+    args: impl IntoIterator<Item = Bound<'py, PyAny>>,
+    codec: &Codec,
+) -> PyResult<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut pairs = vec![];
+    let mut args = args.into_iter();
+    while let Some(field) = args.next() {
+        if let Ok(d) = field.cast::<PyDict>() {
+            for (k, v) in d.iter() {
+                pairs.push((k.extract::<Arg>()?.to_vec(), encode(&v, codec)?));
+            }
+            continue;
+        }
+        let Some(value) = args.next() else {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "Missing value for field",
+            ));
+        };
+        pairs.push((field.extract::<Arg>()?.to_vec(), encode(&value, codec)?));
+    }
+    Ok(pairs)
+}
+
+/// Serialize a Python value the way `Codec::Json` decodes it,
+/// Decimal as a number and date, time and datetime as ISO 8601 strings.
+/// A Decimal a JSON number can not hold exactly raises `ValueError`
+pub fn to_json(value: &Bound<PyAny>) -> PyResult<serde_json::Value> {
+    let py = value.py();
+    let invalid = |msg: String| pyo3::exceptions::PyValueError::new_err(msg);
+    let json = if value.is_none() {
+        serde_json::Value::Null
+    } else if let Ok(b) = value.cast::<PyBool>() {
+        serde_json::Value::Bool(b.is_true())
+    } else if let Ok(i) = value.cast::<PyInt>() {
+        match i.extract::<i64>() {
+            Ok(i) => i.into(),
+            Err(_) => i.extract::<u64>()?.into(),
+        }
+    } else if let Ok(f) = value.cast::<PyFloat>() {
+        serde_json::Number::from_f64(f.value())
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| invalid(format!("Out of range float for JSON: {}", f.value())))?
+    } else if let Ok(s) = value.cast::<PyString>() {
+        serde_json::Value::String(s.to_str()?.to_string())
+    } else if let Ok(b) = value.cast::<PyBytes>() {
+        let s = std::str::from_utf8(b.as_bytes()).map_err(|e| invalid(e.to_string()))?;
+        serde_json::Value::String(s.to_string())
+    } else if let Ok(d) = value.cast::<PyDict>() {
+        let mut map = serde_json::Map::new();
+        for (k, v) in d.iter() {
+            let key = match to_json(&k)? {
+                serde_json::Value::String(s) => s,
+                serde_json::Value::Object(_) | serde_json::Value::Array(_) => {
+                    return Err(invalid("Unsupported JSON key".to_string()))
+                }
+                key => key.to_string(),
+            };
+            map.insert(key, to_json(&v)?);
+        }
+        serde_json::Value::Object(map)
+    } else if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+        let items = value.try_iter()?.map(|v| to_json(&v?));
+        serde_json::Value::Array(items.collect::<PyResult<_>>()?)
+    } else if value.is_instance(&py.import("decimal")?.getattr("Decimal")?)? {
+        let s = value.str()?;
+        let n = serde_json::from_str::<serde_json::Number>(s.to_str()?)
+            .map_err(|_| invalid(format!("Unsupported Decimal for JSON: {s}")))?;
+        if n.is_f64() && !value.get_type().call1((n.to_string(),))?.eq(value)? {
+            return Err(invalid(format!(
+                "Decimal {s} loses precision as a JSON number"
+            )));
+        }
+        serde_json::Value::Number(n)
+    } else if value.is_instance(&py.import("datetime")?.getattr("date")?)?
+        || value.is_instance(&py.import("datetime")?.getattr("time")?)?
+    {
+        serde_json::Value::String(value.call_method0("isoformat")?.extract()?)
     } else {
-        py.None()
+        return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+            "Object of type {} is not JSON serializable",
+            value.get_type().name()?
+        )));
+    };
+    Ok(json)
+}
+
+/// Builtin codecs decode invalid data as None, errors of custom codecs are raised
+pub fn decode(py: Python, v: Vec<u8>, codec: Codec) -> PyResult<Py<PyAny>> {
+    if let Codec::Custom(name) = &codec {
+        return codecs::get(name)?.decode(py, &v);
     }
+    Ok(_decode(py, v, codec).unwrap_or_else(|_| py.None()))
 }
 
 fn _to_dict(py: Python<'_>, value: Value, codec: Codec) -> PyResult<Py<PyAny>> {
     let result = match value {
-        Value::BulkString(v) => decode(py, v, codec),
+        Value::BulkString(v) => decode(py, v, codec)?,
         Value::Nil => py.None(),
         Value::Int(i) => i.into_py_any(py)?,
         Value::Array(_) => to_dict(py, value, codec)?,
@@ -141,13 +253,10 @@ fn _to_dict(py: Python<'_>, value: Value, codec: Codec) -> PyResult<Py<PyAny>> {
         Value::Set(_) => to_object(py, value, codec)?,
         Value::Double(f) => f.into_py_any(py)?,
         Value::Boolean(b) => b.into_py_any(py)?,
-        Value::Attribute {
-            data: _,
-            attributes: _,
-        } => todo!(),
-        Value::VerbatimString { format: _, text: _ } => todo!(),
-        Value::BigNumber(_) => todo!(),
-        Value::Push { kind: _, data: _ } => todo!(),
+        Value::Attribute { data, .. } => _to_dict(py, *data, codec)?,
+        Value::VerbatimString { text, .. } => text.into_py_any(py)?,
+        Value::BigNumber(n) => py.get_type::<PyInt>().call1((n.to_string(),))?.unbind(),
+        Value::Push { .. } => to_object(py, value, codec)?,
         Value::ServerError(err) => Err(error::RedisError::RedisError(err.into()))?,
         _ => todo!(),
     };
@@ -195,7 +304,7 @@ pub fn to_dict(py: Python, value: Value, codec: Codec) -> PyResult<Py<PyAny>> {
 
 pub fn to_object(py: Python, value: Value, codec: Codec) -> PyResult<Py<PyAny>> {
     let result = match value {
-        Value::BulkString(v) => decode(py, v, codec),
+        Value::BulkString(v) => decode(py, v, codec)?,
         Value::Nil => py.None(),
         Value::Int(i) => i.into_py_any(py)?,
         Value::Array(bulk) => {
@@ -217,19 +326,53 @@ pub fn to_object(py: Python, value: Value, codec: Codec) -> PyResult<Py<PyAny>>
         }
         Value::Double(f) => f.into_py_any(py)?,
         Value::Boolean(b) => b.into_py_any(py)?,
-        Value::Attribute {
-            data: _,
-            attributes: _,
-        } => todo!(),
-        Value::VerbatimString { format: _, text: _ } => todo!(),
-        Value::BigNumber(_) => todo!(),
-        Value::Push { kind: _, data: _ } => todo!(),
+        Value::Attribute { data, .. } => to_object(py, *data, codec)?,
+        Value::VerbatimString { text, .. } => text.into_py_any(py)?,
+        Value::BigNumber(n) => py.get_type::<PyInt>().call1((n.to_string(),))?.unbind(),
+        Value::Push { kind, data } => {
+            let mut result = vec![kind.to_string().into_py_any(py)?];
+            for v in data.into_iter() {
+                result.push(to_object(py, v, codec.clone())?);
+            }
+            PyList::new(py, result)?.into_py_any(py)?
+        }
         Value::ServerError(err) => Err(error::RedisError::RedisError(err.into()))?,
         _ => todo!(),
     };
     Ok(result)
 }
 
+/// Reply value from a Python object, used for replies returned by hooks
+pub fn to_value(obj: &Bound<PyAny>) -> PyResult<Value> {
+    let value = if obj.is_none() {
+        Value::Nil
+    } else if let Ok(b) = obj.cast::<PyBool>() {
+        Value::Boolean(b.is_true())
+    } else if let Ok(i) = obj.extract::<i64>() {
+        Value::Int(i)
+    } else if let Ok(f) = obj.cast::<PyFloat>() {
+        Value::Double(f.value())
+    } else if let Ok(s) = obj.cast::<PyString>() {
+        Value::BulkString(s.to_str()?.as_bytes().to_vec())
+    } else if let Ok(b) = obj.cast::<PyBytes>() {
+        Value::BulkString(b.as_bytes().to_vec())
+    } else if let Ok(d) = obj.cast::<PyDict>() {
+        let mut items = vec![];
+        for (k, v) in d.iter() {
+            items.push((to_value(&k)?, to_value(&v)?));
+        }
+        Value::Map(items)
+    } else if let Ok(items) = obj.try_iter() {
+        Value::Array(items.map(|v| to_value(&v?)).collect::<PyResult<_>>()?)
+    } else {
+        return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+            "Unsupported reply type: {}",
+            obj.get_type().name()?
+        )));
+    };
+    Ok(value)
+}
+
 #[derive(FromPyObject)]
 pub enum Str {
     #[pyo3(transparent, annotation = "bytes")]
@@ -21,7 +21,10 @@ async def get_redis_version(nodes: list) -> str:
         features=FEATURES,
     ) as c:
         infos = await c.execute("INFO", "SERVER", encoding="info")
-        if isinstance(infos, dict):
+        if isinstance(infos, dict) and key not in infos:
+            # cluster replies are keyed by node address
+            result = min(info[key] for info in infos.values())
+        elif isinstance(infos, dict):
             result = infos.get(key, "")
         else:
             assert isinstance(infos, list)
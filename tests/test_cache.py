@@ -0,0 +1,77 @@
+from uuid import uuid4
+
+import pytest
+
+import redis_rs
+
+
+@pytest.fixture
+async def cached_client(client_factory):
+    async with client_factory(client_cache={"max_entries": 2}) as c:
+        yield c
+
+
+async def test_cache_hit(cached_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    await cached_client.set(key, "1")
+    assert await cached_client.get(key, encoding="int") == 1
+    assert await cached_client.get(key, encoding="int") == 1
+    status = cached_client.status()["client_cache"]
+    assert status["hits"] == 1
+    assert status["misses"] == 1
+    await cached_client.delete(key)
+
+
+async def test_cache_invalidate(cached_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    await cached_client.set(key, "1")
+    assert await cached_client.get(key, encoding="int") == 1
+    await cached_client.set(key, "2")
+    assert await cached_client.get(key, encoding="int") == 2
+    await cached_client.delete(key)
+
+
+async def test_cache_invalidate_pipeline(cached_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    await cached_client.set(key, "1")
+    assert await cached_client.get(key, encoding="int") == 1
+    await cached_client.pipeline().add("SET", key, 2).execute()
+    assert await cached_client.get(key, encoding="int") == 2
+    await cached_client.delete(key)
+
+
+@pytest.mark.redis(single=True)
+async def test_cache_invalidate_transaction(cached_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    await cached_client.set(key, "1")
+    assert await cached_client.get(key, encoding="int") == 1
+    async with cached_client.transaction() as tx:
+        tx.add("SET", key, 2)
+        await tx.exec()
+    assert await cached_client.get(key, encoding="int") == 2
+    await cached_client.delete(key)
+
+
+async def test_cache_invalidate_script(cached_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    await cached_client.set(key, "1")
+    assert await cached_client.get(key, encoding="int") == 1
+    await cached_client.eval("return redis.call('SET', KEYS[1], ARGV[1])", 1, key, 2)
+    assert await cached_client.get(key, encoding="int") == 2
+    await cached_client.delete(key)
+
+
+async def test_cache_eviction(cached_client: redis_rs.AsyncClient):
+    keys = [uuid4().hex for _ in range(3)]
+    for key in keys:
+        await cached_client.set(key, "1")
+        await cached_client.get(key)
+    status = cached_client.status()["client_cache"]
+    assert status["evictions"] == 1
+    assert status["size"] == 2
+    await cached_client.delete(*keys)
+
+
+def test_cache_options():
+    with pytest.raises(redis_rs.exceptions.RedisError):
+        redis_rs.create_client(client_cache={"size": 1})
@@ -1,3 +1,6 @@
+import asyncio
+from uuid import uuid4
+
 import pytest
 
 import redis_rs
@@ -21,3 +24,107 @@ async def test_password(async_client: redis_rs.AsyncClient, client_factory):
         status = client.status()
         assert status.get("username")
         assert status.get("auth")
+
+
+@pytest.mark.redis(version=6)
+async def test_protocol_3(client_factory):
+    key = uuid4().hex
+    async with client_factory(protocol=3) as client:
+        await client.hset(key, "f", 1)
+        assert await client.execute("HGETALL", key) == {"f": b"1"}
+        await client.delete(key)
+
+
+def test_protocol_invalid():
+    with pytest.raises(redis_rs.exceptions.RedisError):
+        redis_rs.create_client(protocol=4)
+
+
+async def test_retry(client_factory):
+    key = uuid4().hex
+    async with client_factory(retry_attempts=2, retry_backoff=0.01, retry_on=["io"]) as client:
+        await client.set(key, 1)
+        assert await client.get(key, encoding="int") == 1
+        await client.delete(key)
+
+
+def test_retry_on_invalid():
+    with pytest.raises(redis_rs.exceptions.RedisError):
+        redis_rs.create_client(retry_on=["moved"])
+
+
+async def test_timeout_blocking(async_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    # the server-side timeout of BLPOP is added to the client timeout
+    assert await async_client.execute("BLPOP", key, 0.2, timeout=0.05) is None
+
+
+async def test_response_timeout(client_factory):
+    key = uuid4().hex
+    async with client_factory(response_timeout=1.0, connect_timeout=1.0) as client:
+        assert await client.fetch_str("GET", key, timeout=0.5) is None
+    assert issubclass(redis_rs.exceptions.TimeoutError, redis_rs.exceptions.RedisError)
+
+
+async def test_timeout_invalid(async_client: redis_rs.AsyncClient):
+    for timeout in [-1.0, float("nan"), float("inf")]:
+        with pytest.raises(ValueError):
+            await async_client.execute("GET", "a", timeout=timeout)
+        with pytest.raises(ValueError):
+            redis_rs.create_client(response_timeout=timeout)
+        with pytest.raises(ValueError):
+            redis_rs.create_client(connect_timeout=timeout)
+
+
+async def test_health_check(client_factory):
+    async with client_factory(health_check_interval=0.05, health_check_failures=2) as client:
+        await asyncio.sleep(0.2)
+        status = client.status()
+        if status["impl"] in ("mock", "cluster_async"):
+            pytest.skip("Nodes are not pinged by this client")
+        assert status["health"]
+        for health in status["health"].values():
+            assert health["healthy"]
+            assert health["last_ping"]
+
+
+def test_health_check_invalid():
+    for interval in [0, -1.0, float("nan"), float("inf")]:
+        with pytest.raises(ValueError):
+            redis_rs.create_client(health_check_interval=interval)
+    with pytest.raises(ValueError):
+        redis_rs.create_client(health_check_interval=1.0, health_check_failures=0)
+
+
+async def test_metrics(async_client):
+    key = uuid4().hex
+    await async_client.set(key, 1)
+    await async_client.get(key)
+    metrics = async_client.metrics()
+    for series in metrics["GET"].values():
+        assert series["count"] >= 1
+        assert series["buckets"][float("inf")] == series["count"]
+    assert "redis_rs_commands_total{command=\"GET\"" in async_client.metrics_prometheus()
+
+
+def count(client, command):
+    return sum(series["count"] for series in client.metrics().get(command, {}).values())
+
+
+async def test_metrics_batch(async_client):
+    key = uuid4().hex
+    await async_client.pipeline().add("SET", key, 1).add("EXISTS", key).execute()
+    assert count(async_client, "SET") == 1
+    assert count(async_client, "EXISTS") == 1
+    await async_client.hset(key + ":hash", {"a": 1})
+    assert [pair async for pair in async_client.hscan_iter(key + ":hash")]
+    assert count(async_client, "HSCAN") >= 1
+
+
+@pytest.mark.redis(single=True)
+async def test_metrics_transaction(async_client):
+    key = uuid4().hex
+    async with async_client.transaction() as tx:
+        tx.add("SET", key, 1).add("STRLEN", key)
+        await tx.exec()
+    assert count(async_client, "STRLEN") == 1
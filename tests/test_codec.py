@@ -1,3 +1,5 @@
+from datetime import datetime
+from decimal import Decimal
 from uuid import uuid4
 
 import pytest
@@ -18,3 +20,92 @@ async def test_parse_json(async_client: redis_rs.AsyncClient):
     result = await async_client.get(key, encoding="json")
     assert isinstance(result, dict)
     assert result == {"a": 3, "i": ["b"]}
+
+
+@pytest.mark.parametrize("encoding", ["pickle", "msgpack"])
+async def test_builtin_codec(async_client: redis_rs.AsyncClient, encoding):
+    key = uuid4().hex
+    value = {"a": [1, -2, 3.5, None, True], "b": b"\x00\xff", "c": "x" * 100, "d": 2**40}
+    await async_client.set(key, value, encoding=encoding)
+    assert await async_client.get(key, encoding=encoding) == value
+
+
+async def test_register_codec(async_client: redis_rs.AsyncClient):
+    redis_rs.register_codec("upper", lambda b: b.decode().lower(), lambda s: s.upper().encode())
+    key = uuid4().hex
+    await async_client.rpush(key, "value", encoding="upper")
+    assert await async_client.lrange(key, encoding="upper") == ["value"]
+    assert await async_client.lrange(key) == [b"VALUE"]
+    for name in ("json", "bytes", "utf-8", "pickle"):
+        with pytest.raises(ValueError):
+            redis_rs.register_codec(name, lambda b: b)
+
+
+async def test_custom_codec_error(async_client: redis_rs.AsyncClient):
+    def fail(b):
+        raise ZeroDivisionError
+
+    redis_rs.register_codec("fail", fail)
+    key = uuid4().hex
+    await async_client.set(key, "value")
+    with pytest.raises(ZeroDivisionError):
+        await async_client.get(key, encoding="fail")
+
+
+async def test_json_args(async_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    value = {
+        "a": [1, 2.5, None, True],
+        "d": Decimal("1.25"),
+        "t": datetime(2024, 1, 2, 3, 4, 5),
+    }
+    await async_client.set(key, value, encoding="json")
+    assert await async_client.get(key, encoding="json") == {
+        "a": [1, 2.5, None, True],
+        "d": 1.25,
+        "t": "2024-01-02T03:04:05",
+    }
+    with pytest.raises(TypeError):
+        await async_client.set(key, object(), encoding="json")
+    with pytest.raises(ValueError):
+        await async_client.set(key, Decimal("0.10000000000000000001"), encoding="json")
+
+
+async def test_json_str(async_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    await async_client.set(key, "hello", encoding="json")
+    assert await async_client.get(key) == b'"hello"'
+    assert await async_client.get(key, encoding="json") == "hello"
+    await async_client.set(key, b"hello", encoding="json")
+    assert await async_client.get(key, encoding="json") == "hello"
+
+
+async def test_json_raw(async_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    # already serialized JSON is stored without encoding
+    await async_client.set(key, b"[1, 2]")
+    assert await async_client.get(key, encoding="json") == [1, 2]
+
+
+async def test_json_hset(async_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    await async_client.hset(key, "a", [1, 2], mapping={"b": {"c": None}}, encoding="json")
+    assert await async_client.hget(key, "a", encoding="json") == [1, 2]
+    assert await async_client.hgetall(key, encoding="json") == {"a": [1, 2], "b": {"c": None}}
+
+
+async def test_msgpack_depth(async_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    await async_client.set(key, b"\x91" * 100_000 + b"\xc0")
+    with pytest.raises(ValueError):
+        await async_client.get(key, encoding="msgpack")
+    key = uuid4().hex
+    nested = [None]
+    for _ in range(100):
+        nested = [nested]
+    await async_client.set(key, nested, encoding="msgpack")
+    assert await async_client.get(key, encoding="msgpack") == nested
+    cyclic = []
+    cyclic.append(cyclic)
+    with pytest.raises(ValueError):
+        await async_client.set(key, cyclic, encoding="msgpack")
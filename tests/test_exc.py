@@ -1,9 +1,43 @@
 import pytest
 
-from redis_rs import AsyncClient
+from redis_rs import AsyncClient, exceptions
 from redis_rs.exceptions import RedisError
 
+from .conftest import IS_CLUSTER
+
 
 async def test_redis_error(async_client: AsyncClient):
     with pytest.raises(RedisError):
         await async_client.fetch_int("CLUSTER", "SLOTS")
+
+
+async def test_no_script_error(async_client: AsyncClient):
+    with pytest.raises(exceptions.NoScriptError):
+        await async_client.evalsha("0" * 40, 0)
+
+
+def test_hierarchy():
+    assert issubclass(exceptions.ConnectionError, RedisError)
+    assert issubclass(exceptions.TimeoutError, RedisError)
+    assert issubclass(exceptions.AuthenticationError, exceptions.ConnectionError)
+    for exc in (
+        exceptions.WrongTypeError,
+        exceptions.NoScriptError,
+        exceptions.BusyLoadingError,
+        exceptions.ClusterDownError,
+        exceptions.MovedError,
+        exceptions.AskError,
+        exceptions.ReadOnlyError,
+    ):
+        assert issubclass(exc, exceptions.ResponseError)
+        assert issubclass(exc, RedisError)
+
+
+async def test_attributes(async_client: AsyncClient):
+    with pytest.raises(exceptions.NoScriptError) as e:
+        await async_client.evalsha("0" * 40, 0)
+    assert e.value.code == "NOSCRIPT"
+    assert e.value.command == "EVALSHA"
+    # the cluster connection routes keyless commands itself, the node is not known
+    assert e.value.node or IS_CLUSTER
+    assert e.value.retryable is False
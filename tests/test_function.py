@@ -0,0 +1,27 @@
+import redis_rs
+
+CODE = """#!lua name=test_lib
+redis.register_function{
+    function_name='test_echo',
+    callback=function(keys, args) return args[1] end,
+    flags={'no-writes'},
+}
+"""
+
+
+async def test_fcall(async_client: redis_rs.AsyncClient):
+    assert await async_client.function_load(CODE, replace=True) == "test_lib"
+    result = await async_client.fcall("test_echo", 1, "key", "a", encoding="utf-8")
+    assert result == "a"
+    result = await async_client.fcall_ro("test_echo", 0, "b", encoding="utf-8")
+    assert result == "b"
+    assert await async_client.function_delete("test_lib")
+
+
+async def test_function_list(async_client: redis_rs.AsyncClient):
+    await async_client.function_load(CODE, replace=True)
+    libraries = await async_client.function_list("test_lib", encoding="utf-8")
+    assert len(libraries) == 1
+    assert libraries[0]["library_name"] == "test_lib"
+    assert libraries[0]["functions"][0]["name"] == "test_echo"
+    await async_client.function_delete("test_lib")
@@ -0,0 +1,69 @@
+from uuid import uuid4
+
+import pytest
+
+import redis_rs
+
+
+class Recorder:
+    def __init__(self):
+        self.calls = []
+
+    def after(self, call, duration, error):
+        self.calls.append((call.name, call.keys, duration, error))
+
+
+class Fault:
+    def __init__(self):
+        self.fault = uuid4().hex.encode()
+        self.stub = uuid4().hex.encode()
+        self.prefix = uuid4().hex.encode()
+
+    def before(self, call):
+        if call.name == "GET" and call.keys == [self.fault]:
+            raise ConnectionError("injected")
+        if call.name == "GET" and call.keys == [self.stub]:
+            return "stubbed"
+        if call.name == "SET" and call.keys == [self.prefix]:
+            call.args = ["rewritten:" + call.args[0].decode(), *call.args[1:]]
+
+
+class AsyncHook:
+    async def before(self, call):
+        pass
+
+
+async def test_after(async_client):
+    key = uuid4().hex
+    recorder = Recorder()
+    async_client.add_hook(recorder)
+    await async_client.set(key, 1)
+    assert async_client.remove_hook(recorder)
+    await async_client.get(key)
+    assert len(recorder.calls) == 1
+    name, keys, duration, error = recorder.calls[0]
+    assert (name, keys, error) == ("SET", [key.encode()], None)
+    assert duration >= 0
+
+
+async def test_after_error(async_client):
+    recorder = Recorder()
+    async_client.add_hook(recorder)
+    with pytest.raises(redis_rs.exceptions.RedisError):
+        await async_client.evalsha("0" * 40, 0)
+    assert isinstance(recorder.calls[-1][3], redis_rs.exceptions.RedisError)
+
+
+async def test_before(async_client):
+    fault = Fault()
+    async_client.add_hook(fault)
+    with pytest.raises(ConnectionError):
+        await async_client.get(fault.fault)
+    assert await async_client.get(fault.stub, encoding="utf-8") == "stubbed"
+    await async_client.set(fault.prefix, 1)
+    assert await async_client.get(b"rewritten:" + fault.prefix, encoding="int") == 1
+
+
+async def test_async_hook(async_client):
+    with pytest.raises(TypeError):
+        async_client.add_hook(AsyncHook())
@@ -0,0 +1,38 @@
+from uuid import uuid4
+
+import redis_rs
+
+
+async def test_pipeline(async_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    pipeline = async_client.pipeline()
+    pipeline.add("SET", key, 1).add("GET", key).add("GET", key, encoding="int")
+    assert len(pipeline) == 3
+
+    result = await pipeline.execute()
+    assert result[1:] == [b"1", 1]
+    assert len(pipeline) == 0
+
+
+async def test_pipeline_order(async_client: redis_rs.AsyncClient):
+    keys = [uuid4().hex for _ in range(10)]
+    pipeline = async_client.pipeline()
+    for n, key in enumerate(keys):
+        pipeline.add("SET", key, n)
+    for key in keys:
+        pipeline.add("GET", key, encoding="int")
+
+    result = await pipeline.execute()
+    assert result[len(keys) :] == list(range(len(keys)))
+
+
+async def test_pipeline_read_after_write(async_client: redis_rs.AsyncClient):
+    keys = [uuid4().hex for _ in range(10)]
+    pipeline = async_client.pipeline()
+    for n, key in enumerate(keys):
+        pipeline.add("SET", key, n).add("GET", key, encoding="int")
+        pipeline.add("SET", key, n + 1).add("GET", key, encoding="int")
+
+    result = await pipeline.execute()
+    assert result[1::4] == list(range(len(keys)))
+    assert result[3::4] == list(range(1, len(keys) + 1))
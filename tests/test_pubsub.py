@@ -0,0 +1,29 @@
+import asyncio
+from uuid import uuid4
+
+import redis_rs
+
+
+async def test_subscribe(async_client: redis_rs.AsyncClient):
+    channel = uuid4().hex
+    subscription = await async_client.subscribe(channel, encoding="utf-8")
+    await async_client.publish(channel, "hello")
+
+    message = await asyncio.wait_for(subscription.__anext__(), 1)
+    assert message == {"type": "message", "pattern": None, "channel": channel, "data": "hello"}
+    subscription.close()
+
+
+async def test_psubscribe(async_client: redis_rs.AsyncClient):
+    prefix = uuid4().hex
+    subscription = await async_client.psubscribe(f"{prefix}*")
+    await async_client.publish(f"{prefix}-1", 1)
+    await async_client.publish(f"{prefix}-2", 2)
+
+    result = []
+    async for message in subscription:
+        assert message["pattern"] == f"{prefix}*"
+        result.append(message["data"])
+        if len(result) == 2:
+            subscription.close()
+    assert result == [b"1", b"2"]
@@ -0,0 +1,30 @@
+from uuid import uuid4
+
+import redis_rs
+
+
+async def test_scan_iter(async_client: redis_rs.AsyncClient):
+    prefix = uuid4().hex
+    keys = {f"{prefix}:{i}" for i in range(20)}
+    for key in keys:
+        await async_client.set(key, 1)
+
+    result = [key async for key in async_client.scan_iter(match=f"{prefix}:*", count=5, encoding="utf-8")]
+    assert len(result) == len(keys)
+    assert set(result) == keys
+
+
+async def test_hscan_iter(async_client: redis_rs.AsyncClient):
+    key = str(uuid4())
+    await async_client.hset(key, {"a": 1, "b": 2})
+
+    result = [pair async for pair in async_client.hscan_iter(key, encoding="int")]
+    assert sorted(result) == [("a", 1), ("b", 2)]
+
+
+async def test_zscan_iter(async_client: redis_rs.AsyncClient):
+    key = str(uuid4())
+    await async_client.zadd(key, {"a": 1.5, "b": 2})
+
+    result = [pair async for pair in async_client.zscan_iter(key, encoding="utf-8")]
+    assert sorted(result) == [("a", 1.5), ("b", 2.0)]
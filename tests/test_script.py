@@ -4,3 +4,35 @@ import redis_rs
 async def test_return(async_client: redis_rs.AsyncClient):
     result = await async_client.eval("return ARGV[1]", 0, "a", encoding="utf-8")
     assert result == "a"
+
+
+async def test_register_script(async_client: redis_rs.AsyncClient):
+    script = async_client.register_script("return ARGV[1]")
+    await async_client.script_flush()
+    assert await async_client.script_exists(script.sha) == [False]
+    result = await script(args=["a"], encoding="utf-8")
+    assert result == "a"
+    assert await async_client.script_exists(script.sha) == [True]
+
+
+async def test_evalsha(async_client: redis_rs.AsyncClient):
+    script = async_client.register_script("return ARGV[1]")
+    await script(args=["a"])
+    result = await async_client.evalsha(script.sha, 0, "b", encoding="utf-8")
+    assert result == "b"
+
+
+async def test_register_script_hooks(async_client: redis_rs.AsyncClient):
+    names = []
+
+    class Hook:
+        def before(self, call):
+            names.append(call.name)
+
+    script = async_client.register_script("return ARGV[1]")
+    await async_client.script_flush()
+    async_client.add_hook(Hook())
+    result = await script(args=["a"], encoding="utf-8")
+    assert result == "a"
+    # SCRIPT LOAD completes before EVALSHA is retried
+    assert names == ["EVALSHA", "SCRIPT", "EVALSHA"]
@@ -0,0 +1,61 @@
+import os
+from uuid import uuid4
+
+import pytest
+
+import redis_rs
+
+# rediss:// address of a TLS endpoint, the tests are skipped without it
+TLS_NODE = os.environ.get("REDIS_TLS_NODE", "")
+TLS_CA_CERTS = os.environ.get("REDIS_TLS_CA_CERTS")
+TLS_CERTFILE = os.environ.get("REDIS_TLS_CERTFILE")
+TLS_KEYFILE = os.environ.get("REDIS_TLS_KEYFILE")
+
+
+@pytest.fixture
+def tls_factory():
+    if not TLS_NODE:
+        pytest.skip("REDIS_TLS_NODE is not set")
+
+    def factory(*nodes, **kwargs):
+        return redis_rs.create_client(*(nodes or [TLS_NODE]), **kwargs)
+
+    return factory
+
+
+async def roundtrip(client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    await client.set(key, "1")
+    assert await client.get(key, encoding="int") == 1
+    await client.delete(key)
+
+
+async def test_tls_certificates(tls_factory):
+    async with tls_factory(
+        tls_ca_certs=TLS_CA_CERTS,
+        tls_certfile=TLS_CERTFILE,
+        tls_keyfile=TLS_KEYFILE,
+    ) as c:
+        await roundtrip(c)
+
+
+async def test_tls_insecure(tls_factory):
+    async with tls_factory(
+        tls_certfile=TLS_CERTFILE,
+        tls_keyfile=TLS_KEYFILE,
+        tls_insecure=True,
+    ) as c:
+        await roundtrip(c)
+
+
+async def test_tls_flag(tls_factory):
+    # a plain redis:// address is upgraded by tls=True
+    node = TLS_NODE.replace("rediss://", "redis://", 1)
+    async with tls_factory(
+        node,
+        tls=True,
+        tls_ca_certs=TLS_CA_CERTS,
+        tls_certfile=TLS_CERTFILE,
+        tls_keyfile=TLS_KEYFILE,
+    ) as c:
+        await roundtrip(c)
@@ -0,0 +1,59 @@
+from uuid import uuid4
+
+import pytest
+
+import redis_rs
+from redis_rs.exceptions import WatchError
+
+
+@pytest.mark.redis(single=True)
+async def test_transaction(async_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    async with async_client.transaction() as tx:
+        tx.add("SET", key, 1).add("GET", key).add("GET", key, encoding="int")
+        assert len(tx) == 3
+        result = await tx.exec()
+
+    assert result[1:] == [b"1", 1]
+
+
+@pytest.mark.redis(single=True)
+async def test_transaction_watch(async_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    with pytest.raises(WatchError):
+        async with async_client.transaction(key) as tx:
+            await tx.execute("GET", key)
+            await async_client.execute("SET", key, 1)
+            tx.add("SET", key, 2)
+            await tx.exec()
+
+    assert await async_client.fetch_int("GET", key) == 1
+
+
+@pytest.mark.redis(single=True)
+async def test_transaction_retry(async_client: redis_rs.AsyncClient):
+    key = uuid4().hex
+    attempts = 0
+    async for tx in async_client.transaction(key, retries=2):
+        async with tx:
+            attempts += 1
+            value = await tx.execute("GET", key, encoding="int") or 0
+            if attempts == 1:
+                await async_client.execute("SET", key, 10)
+            tx.add("SET", key, value + 1)
+            await tx.exec()
+
+    assert attempts == 2
+    assert await async_client.fetch_int("GET", key) == 11
+
+
+@pytest.mark.redis(cluster=True)
+async def test_transaction_cross_slot(client_factory):
+    key = uuid4().hex
+    # transactions in cluster mode are served by the shards pool
+    async with client_factory(features=["shards"]) as client:
+        with pytest.raises(redis_rs.exceptions.RedisError, match="CROSSSLOT"):
+            async with client.transaction() as tx:
+                await tx.execute("PING")
+                await tx.execute("GET", f"{{{key}}}1")
+                await tx.execute("GET", f"{key}2")
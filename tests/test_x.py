@@ -151,3 +151,27 @@ async def test_xreadgroup(async_client: redis_rs.AsyncClient):
     result = await async_client.xread(stream, group=group, count=2, block=5000)
     assert result == {stream: {ident: {"a": b"3"}}}
     assert isinstance(result, dict)
+
+
+async def test_xadd_json(async_client: redis_rs.AsyncClient):
+    stream = str(uuid4())
+
+    ident = await async_client.xadd(stream, {"a": {"b": [1, None]}}, encoding="json")
+    result = await async_client.xread({stream: 0}, encoding="json")
+    assert result == {stream: {ident: {"a": {"b": [1, None]}}}}
+
+
+async def test_xread_entries(async_client: redis_rs.AsyncClient):
+    stream = str(uuid4())
+
+    assert await async_client.xread({stream: 0}, entries=True) == []
+    first = await async_client.xadd(stream, {"a": "1"})
+    second = await async_client.xadd(stream, {"a": "2"})
+
+    entries = await async_client.xread({stream: 0}, encoding="int", entries=True)
+    assert [e.id for e in entries] == [first, second]
+    assert entries[0].stream == stream
+    assert entries[1].fields == {"a": 2}
+    assert (entries[0].ms, entries[0].seq) == redis_rs.StreamEntry.parse_id(first)
+    assert redis_rs.StreamEntry.parse_id("5-*") == (5, None)
+    assert redis_rs.StreamEntry.parse_id(1.5) == (1, 5)